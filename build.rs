@@ -1,3 +1,9 @@
 fn main() {
-    println!("cargo:rustc-env=RUST_ALLOC=Mimalloc");
-}
\ No newline at end of file
+    // имя базового аллокатора, выбранного через cargo features
+    let alloc = if std::env::var_os("CARGO_FEATURE_MIMALLOC").is_some() {
+        "Mimalloc"
+    } else {
+        "System"
+    };
+    println!("cargo:rustc-env=RUST_ALLOC={alloc}");
+}
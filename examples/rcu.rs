@@ -6,6 +6,9 @@ use std::{
 };
 use std_reset::prelude::Display;
 
+#[global_allocator]
+static GLOBAL: lf_structs::allocator::Selected = lf_structs::allocator::selected();
+
 #[derive(Debug, Clone, Display)]
 struct User {
     id: usize,
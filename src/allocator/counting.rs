@@ -0,0 +1,122 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering::*},
+};
+
+/// Аллокатор-обёртка, который считает выделения и живые байты
+#[derive(Debug)]
+pub struct Counting<A = System> {
+    inner: A,
+    allocations: AtomicUsize,
+    frees: AtomicUsize,
+    live_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+}
+
+/// Снимок счётчиков [`Counting`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub allocations: usize,
+    pub frees: usize,
+    pub live_bytes: usize,
+    pub peak_bytes: usize,
+}
+
+impl<A> Counting<A> {
+    pub const fn new(inner: A) -> Self {
+        Self {
+            inner,
+            allocations: AtomicUsize::new(0),
+            frees: AtomicUsize::new(0),
+            live_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+        }
+    }
+    pub fn stats(&self) -> Stats {
+        Stats {
+            allocations: self.allocations.load(Relaxed),
+            frees: self.frees.load(Relaxed),
+            live_bytes: self.live_bytes.load(Relaxed),
+            peak_bytes: self.peak_bytes.load(Relaxed),
+        }
+    }
+    /// Опускает пик до текущего количества живых байт
+    pub fn reset_peak(&self) {
+        self.peak_bytes
+            .store(self.live_bytes.load(Relaxed), Relaxed);
+    }
+    /// Возвращает пик, который был до [`reset_peak`](Self::reset_peak), если он выше
+    pub(super) fn restore_peak(&self, peak: usize) {
+        self.peak_bytes.fetch_max(peak, Relaxed);
+    }
+    fn on_alloc(&self, size: usize) {
+        self.allocations.fetch_add(1, Relaxed);
+        let live = self.live_bytes.fetch_add(size, Relaxed) + size;
+        self.peak_bytes.fetch_max(live, Relaxed);
+    }
+    fn on_dealloc(&self, size: usize) {
+        self.frees.fetch_add(1, Relaxed);
+        self.live_bytes.fetch_sub(size, Relaxed);
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Counting<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            self.on_alloc(layout.size());
+        }
+        ptr
+    }
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.on_alloc(layout.size());
+        }
+        ptr
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        self.on_dealloc(layout.size());
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            // перевыделение считается как освобождение старого блока и выделение нового
+            self.on_dealloc(layout.size());
+            self.on_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[test]
+fn counts_allocations() {
+    let counting = Counting::new(System);
+    let layout = Layout::array::<u64>(8).unwrap();
+    unsafe {
+        let a = counting.alloc(layout);
+        let b = counting.alloc_zeroed(layout);
+        assert_eq!(
+            counting.stats(),
+            Stats {
+                allocations: 2,
+                frees: 0,
+                live_bytes: 128,
+                peak_bytes: 128,
+            }
+        );
+        let a = counting.realloc(a, layout, 16);
+        counting.dealloc(b, layout);
+        counting.dealloc(a, Layout::from_size_align(16, layout.align()).unwrap());
+    }
+    assert_eq!(
+        counting.stats(),
+        Stats {
+            allocations: 3,
+            frees: 3,
+            live_bytes: 0,
+            peak_bytes: 128,
+        }
+    );
+}
@@ -0,0 +1,50 @@
+//! Выбор глобального аллокатора через cargo features `mimalloc` и `counting`,
+//! чтобы сравнения из `check!` можно было повторить на разных аллокаторах.
+//! Без `mimalloc` используется системный, а `counting` оборачивает выбранный.
+//! Сама библиотека глобальный аллокатор не ставит, это делают примеры через [`selected`].
+//! Тесты библиотеки всегда оборачивают [`Base`] в [`Counting`], на нём работает [`MemoryProbe`]
+pub mod counting;
+pub mod probe;
+
+pub use counting::Counting;
+pub use probe::MemoryProbe;
+
+/// Имя базового аллокатора
+pub const NAME: &str = env!("RUST_ALLOC");
+
+/// Базовый аллокатор: с feature `mimalloc` это mimalloc, иначе системный
+#[cfg(feature = "mimalloc")]
+pub type Base = mimalloc::MiMalloc;
+#[cfg(not(feature = "mimalloc"))]
+pub type Base = std::alloc::System;
+
+#[cfg(feature = "mimalloc")]
+pub const BASE: Base = mimalloc::MiMalloc;
+#[cfg(not(feature = "mimalloc"))]
+pub const BASE: Base = std::alloc::System;
+
+/// Аллокатор для примеров и бенчмарков: с feature `counting` [`Base`] обёрнут в [`Counting`]
+#[cfg(feature = "counting")]
+pub type Selected = Counting<Base>;
+#[cfg(not(feature = "counting"))]
+pub type Selected = Base;
+
+/// Значение для `static` с `#[global_allocator]`. Функция, а не константа:
+/// у [`Counting`] внутренняя изменяемость, и каждая копия константы считала бы своё
+#[cfg(feature = "counting")]
+pub const fn selected() -> Selected {
+    Counting::new(BASE)
+}
+#[cfg(not(feature = "counting"))]
+pub const fn selected() -> Selected {
+    BASE
+}
+
+/// Тесты библиотеки замеряют память через [`MemoryProbe`], поэтому считают всегда
+#[cfg(test)]
+#[global_allocator]
+pub(crate) static GLOBAL: Counting<Base> = Counting::new(BASE);
+
+/// Замеры на [`GLOBAL`] идут по одному, иначе выделения одного теста попадают в замер другого
+#[cfg(test)]
+pub(crate) static MEASURED: std::sync::Mutex<()> = std::sync::Mutex::new(());
@@ -0,0 +1,85 @@
+use super::counting::{Counting, Stats};
+
+/// Замер выделений памяти на участке кода через установленный глобальным [`Counting`].
+///
+/// Счётчики общие для всего процесса, поэтому в замер попадают и выделения
+/// параллельно работающих потоков. Вложенный замер возвращает пик внешнего,
+/// когда заканчивается
+#[derive(Debug)]
+pub struct MemoryProbe<'a, A> {
+    alloc: &'a Counting<A>,
+    start: Stats,
+    // пик до начала замера, он ещё нужен внешнему замеру
+    outer_peak: usize,
+}
+
+/// Результат замера участка кода
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub allocations: usize,
+    pub frees: usize,
+    /// На сколько изменилось количество живых байт, т.е. утечка участка
+    pub live_bytes: isize,
+    /// Пик живых байт относительно начала замера
+    pub peak_bytes: usize,
+}
+
+impl<'a, A> MemoryProbe<'a, A> {
+    pub fn start(alloc: &'a Counting<A>) -> Self {
+        let outer_peak = alloc.stats().peak_bytes;
+        alloc.reset_peak();
+        Self {
+            alloc,
+            start: alloc.stats(),
+            outer_peak,
+        }
+    }
+    pub fn finish(self) -> Region {
+        let end = self.alloc.stats();
+        self.alloc.restore_peak(self.outer_peak);
+        Region {
+            allocations: end.allocations - self.start.allocations,
+            frees: end.frees - self.start.frees,
+            live_bytes: end.live_bytes as isize - self.start.live_bytes as isize,
+            peak_bytes: end.peak_bytes.saturating_sub(self.start.live_bytes),
+        }
+    }
+}
+
+/// Выполняет `f` и замеряет выделения памяти внутри него
+pub fn measure<A, R>(alloc: &Counting<A>, f: impl FnOnce() -> R) -> (R, Region) {
+    let probe = MemoryProbe::start(alloc);
+    let res = f();
+    (res, probe.finish())
+}
+
+#[test]
+fn measure_region() {
+    let _measured = super::MEASURED.lock().unwrap_or_else(|e| e.into_inner());
+    let (leaked, region) = measure(&super::GLOBAL, || {
+        // в release без black_box выделение и освобождение выбрасываются
+        let temp = std::hint::black_box(vec![0u8; 1 << 20]);
+        drop(temp);
+        Box::leak(std::hint::black_box(Box::new([0u8; 1024])))
+    });
+    assert!(region.allocations >= 2);
+    assert!(region.frees >= 1);
+    assert!(region.peak_bytes >= 1 << 20);
+    // параллельные тесты могут освобождать память во время замера,
+    // поэтому утечку проверяем только через пик
+    assert!(region.live_bytes <= region.peak_bytes as isize);
+    assert_eq!(leaked.len(), 1024);
+}
+
+#[test]
+fn nested_probe_keeps_outer_peak() {
+    use std::hint::black_box;
+
+    let _measured = super::MEASURED.lock().unwrap_or_else(|e| e.into_inner());
+    let outer = MemoryProbe::start(&super::GLOBAL);
+    drop(black_box(vec![0u8; 4 << 20]));
+    let (_, inner) = measure(&super::GLOBAL, || drop(black_box(vec![0u8; 1024])));
+    let outer = outer.finish();
+    assert!(inner.peak_bytes < 4 << 20);
+    assert!(outer.peak_bytes >= 4 << 20);
+}
@@ -0,0 +1,77 @@
+use atomic_wait::{wait, wake_all};
+use std::sync::atomic::{AtomicU32, Ordering::*};
+
+/// Многоразовый барьер на `n` потоков: каждый раунд пропускает всех,
+/// когда подойдёт последний, и выбирает его лидером
+pub struct Barrier {
+    n: u32,
+    arrived: AtomicU32,
+    // номер раунда, на нём и спят ожидающие
+    generation: AtomicU32,
+}
+
+/// Результат [`Barrier::wait`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Ровно один поток в каждом раунде оказывается лидером
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    pub const fn new(n: u32) -> Self {
+        assert!(n != 0);
+        Self {
+            n,
+            arrived: AtomicU32::new(0),
+            generation: AtomicU32::new(0),
+        }
+    }
+    /// Ждёт, пока к барьеру подойдут все `n` потоков.
+    /// Пока раунд не закончился, новых потоков быть не должно
+    pub fn wait(&self) -> BarrierWaitResult {
+        // раунд не закончится без нас, поэтому его номер читаем до прихода
+        let generation = self.generation.load(Acquire);
+        if self.arrived.fetch_add(1, AcqRel) + 1 == self.n {
+            self.arrived.store(0, Relaxed);
+            self.generation.fetch_add(1, Release);
+            wake_all(&self.generation);
+            return BarrierWaitResult(true);
+        }
+        while self.generation.load(Acquire) == generation {
+            wait(&self.generation, generation);
+        }
+        BarrierWaitResult(false)
+    }
+}
+
+#[test]
+fn rounds_and_leaders() {
+    use std::thread;
+
+    const THREADS: u32 = 8;
+    const ROUNDS: u32 = 100;
+
+    let barrier = Barrier::new(THREADS);
+    let leaders = AtomicU32::new(0);
+    let arrived = AtomicU32::new(0);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for round in 0..ROUNDS {
+                    arrived.fetch_add(1, Relaxed);
+                    if barrier.wait().is_leader() {
+                        leaders.fetch_add(1, Relaxed);
+                    }
+                    // никто не обгоняет раунд
+                    assert!(arrived.load(Relaxed) >= (round + 1) * THREADS);
+                    barrier.wait();
+                }
+            });
+        }
+    });
+    assert_eq!(leaders.into_inner(), ROUNDS);
+}
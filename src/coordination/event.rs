@@ -0,0 +1,103 @@
+use atomic_wait::{wait, wake_all, wake_one};
+use std::sync::atomic::{AtomicU32, Ordering::*};
+
+/// Событие, которого ждут потоки.
+///
+/// С ручным сбросом поднятое событие пропускает всех, пока его не сбросят.
+/// С автоматическим каждый подъём пропускает ровно одного ожидающего
+pub struct Event {
+    state: AtomicU32,
+    auto_reset: bool,
+}
+
+const UNSET: u32 = 0;
+const SET: u32 = 1;
+
+impl Event {
+    pub const fn manual(set: bool) -> Self {
+        Self::new(set, false)
+    }
+    pub const fn auto(set: bool) -> Self {
+        Self::new(set, true)
+    }
+    const fn new(set: bool, auto_reset: bool) -> Self {
+        Self {
+            state: AtomicU32::new(if set { SET } else { UNSET }),
+            auto_reset,
+        }
+    }
+    pub fn set(&self) {
+        self.state.store(SET, Release);
+        if self.auto_reset {
+            wake_one(&self.state);
+        } else {
+            wake_all(&self.state);
+        }
+    }
+    pub fn reset(&self) {
+        self.state.store(UNSET, Relaxed);
+    }
+    pub fn is_set(&self) -> bool {
+        self.state.load(Acquire) == SET
+    }
+    /// Ждёт подъёма, а при автоматическом сбросе ещё и сбрасывает событие
+    pub fn wait(&self) {
+        while !self.try_wait() {
+            wait(&self.state, UNSET);
+        }
+    }
+    /// Как [`Self::wait`], но не ждёт
+    pub fn try_wait(&self) -> bool {
+        if self.auto_reset {
+            self.state
+                .compare_exchange(SET, UNSET, Acquire, Relaxed)
+                .is_ok()
+        } else {
+            self.is_set()
+        }
+    }
+}
+
+#[test]
+fn manual_reset() {
+    use std::thread;
+
+    let event = Event::manual(false);
+    assert!(!event.try_wait());
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| event.wait());
+        }
+        event.set();
+    });
+    assert!(event.is_set());
+    event.wait();
+    event.reset();
+    assert!(!event.try_wait());
+}
+
+#[test]
+fn auto_reset_passes_one() {
+    use std::thread;
+
+    let event = Event::auto(true);
+    assert!(event.try_wait());
+    assert!(!event.try_wait());
+
+    let passed = AtomicU32::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                event.wait();
+                passed.fetch_add(1, Relaxed);
+            });
+        }
+        for i in 1..=4 {
+            event.set();
+            while passed.load(Relaxed) != i {
+                thread::yield_now();
+            }
+        }
+    });
+    assert!(!event.is_set());
+}
@@ -0,0 +1,62 @@
+use atomic_wait::{wait, wake_all};
+use std::sync::atomic::{AtomicU32, Ordering::*};
+
+/// Одноразовая защёлка: ожидающие проходят, когда счётчик дойдёт до нуля
+pub struct CountDownLatch {
+    count: AtomicU32,
+}
+
+impl CountDownLatch {
+    pub const fn new(count: u32) -> Self {
+        Self {
+            count: AtomicU32::new(count),
+        }
+    }
+    /// Уменьшает счётчик, последний вызов отпускает всех ожидающих.
+    /// После этого ничего не делает
+    pub fn count_down(&self) {
+        let res = self
+            .count
+            .fetch_update(Release, Relaxed, |c| c.checked_sub(1));
+        if res == Ok(1) {
+            wake_all(&self.count);
+        }
+    }
+    pub fn count(&self) -> u32 {
+        self.count.load(Acquire)
+    }
+    pub fn wait(&self) {
+        loop {
+            let c = self.count.load(Acquire);
+            if c == 0 {
+                return;
+            }
+            wait(&self.count, c);
+        }
+    }
+}
+
+#[test]
+fn releases_all_waiters() {
+    use std::thread;
+
+    let latch = CountDownLatch::new(3);
+    let started = CountDownLatch::new(4);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                started.count_down();
+                latch.wait();
+                assert_eq!(latch.count(), 0);
+            });
+        }
+        started.wait();
+        for _ in 0..3 {
+            assert_ne!(latch.count(), 0);
+            latch.count_down();
+        }
+    });
+    latch.count_down();
+    assert_eq!(latch.count(), 0);
+    latch.wait();
+}
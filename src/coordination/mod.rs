@@ -0,0 +1,10 @@
+//! Примитивы для согласования потоков на futex из atomic-wait,
+//! по образцу [`optimised::Semaphore`](crate::semaphore::optimised::Semaphore)
+
+pub mod barrier;
+pub mod event;
+pub mod latch;
+
+pub use barrier::{Barrier, BarrierWaitResult};
+pub use event::Event;
+pub use latch::CountDownLatch;
@@ -1,8 +1,13 @@
 #![feature(test)]
+#[cfg(test)]
+extern crate test;
 
 pub mod semaphore;
 pub mod rcu;
 pub use rcu::rcu_with_garbage_collector::RcuGC as Rcu;
 pub mod linked_list;
 pub mod queue_based_locks;
+pub mod allocator;
+pub mod coordination;
+pub mod rwlock;
 pub use semaphore::*;
@@ -1,4 +1,7 @@
-use crate::rcu::Rcu;
+use crate::rcu::{
+    alloc::{Global, RcuAlloc},
+    Rcu,
+};
 use std::cell::{Cell, UnsafeCell};
 use std::collections::HashSet;
 use std::fmt::{Debug, Display};
@@ -15,7 +18,7 @@ use std_reset::traits::of_to::{Of, To};
 pub mod new_solution;
 
 #[derive(Debug)]
-struct Node<T> {
+pub struct Node<T> {
     data: Rcu<T>,
     next: AtomicPtr<Node<T>>,
 }
@@ -43,21 +46,22 @@ impl<T: Display + Debug + Clone> Display for Node<T> {
 }
 
 #[derive(Debug)]
-pub struct List<T> {
+pub struct List<T, A: RcuAlloc<Node<T>> = Global> {
     head: AtomicPtr<Node<T>>,
     foot: AtomicPtr<Node<T>>,
+    alloc: A,
 }
 
-unsafe impl<T: Send> Sync for List<T> {}
+unsafe impl<T: Send, A: RcuAlloc<Node<T>> + Sync> Sync for List<T, A> {}
 
-impl<T: Display + Debug + Clone> Display for List<T> {
+impl<T: Display + Debug + Clone, A: RcuAlloc<Node<T>>> Display for List<T, A> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_list().entries(self.to::<Vec<T>>().iter()).finish()
     }
 }
 
-impl<T: Clone> Of<&List<T>> for Vec<T> {
-    fn of(s: &List<T>) -> Vec<T> {
+impl<T: Clone, A: RcuAlloc<Node<T>>> Of<&List<T, A>> for Vec<T> {
+    fn of(s: &List<T, A>) -> Vec<T> {
         let mut d_l = vec![];
         if let Some(mut u) = unsafe { s.head.load(Relaxed).as_ref() } {
             loop {
@@ -75,10 +79,18 @@ impl<T: Clone> Of<&List<T>> for Vec<T> {
 
 impl<T: Clone + Debug> List<T> {
     pub fn new(data: &[T]) -> Self {
+        Self::new_in(data, Global)
+    }
+}
+
+impl<T: Clone + Debug, A: RcuAlloc<Node<T>>> List<T, A> {
+    /// Узлы списка будут размещаться в `alloc`
+    pub fn new_in(data: &[T], alloc: A) -> Self {
         let Some((head, data)) = data.split_first() else {
             return Self {
                 head: AtomicPtr::new(std::ptr::null_mut()),
                 foot: AtomicPtr::new(std::ptr::null_mut()),
+                alloc,
             };
         };
 
@@ -86,30 +98,32 @@ impl<T: Clone + Debug> List<T> {
         let mut curr = &mut node.next;
 
         let Some((foot, data)) = data.split_last() else {
-            let ptr = Box::into_raw(Box::new(node));
+            let ptr = alloc.alloc(node);
             return Self {
                 head: AtomicPtr::new(ptr),
                 foot: AtomicPtr::new(ptr),
+                alloc,
             };
         };
 
         for i in data {
             let node = Node::new(i.clone());
-            curr.swap(Box::into_raw(Box::new(node)), Relaxed);
+            curr.swap(alloc.alloc(node), Relaxed);
             curr = &mut unsafe { &mut *curr.load(Relaxed) }.next;
         }
 
         let foot = Node::new(foot.clone());
-        let f_ptr: *mut Node<T> = Box::into_raw(Box::new(foot));
+        let f_ptr: *mut Node<T> = alloc.alloc(foot);
         curr.swap(f_ptr, Relaxed);
 
         Self {
-            head: AtomicPtr::new(Box::into_raw(Box::new(node))),
+            head: AtomicPtr::new(alloc.alloc(node)),
             foot: AtomicPtr::new(f_ptr),
+            alloc,
         }
     }
     pub fn push_front(&self, data: T) {
-        let new_node = Box::into_raw(Box::new(Node::new(data.clone())));
+        let new_node = self.alloc.alloc(Node::new(data.clone()));
         let mut head = self.head.load(Acquire);
         loop {
             if !head.is_null() {
@@ -134,7 +148,7 @@ impl<T: Clone + Debug> List<T> {
     where
         T: Display,
     {
-        let new_node = Box::into_raw(Box::new(Node::new(data.clone())));
+        let new_node = self.alloc.alloc(Node::new(data.clone()));
 
         loop {
             if let Some(foot_node) = unsafe { self.foot.load(Acquire).as_ref() } {
@@ -196,6 +210,22 @@ fn push_front_back() {
     assert!(vec[vec.len() / 2 - 1] < 0);
 }
 
+#[test]
+fn nodes_from_pool() {
+    let pool = crate::rcu::alloc::Pool::with_capacity(100);
+    let list = &List::new_in(&[0], &pool);
+    thread::scope(|s| {
+        for i in 1..100 {
+            s.spawn(move || {
+                list.push_front(i);
+            });
+        }
+    });
+    assert_eq!(list.to::<Vec<usize>>().len(), 100);
+    assert_eq!(pool.allocated(), 100);
+    assert!(pool.is_empty());
+}
+
 #[test]
 fn push_back() {
     let list = &List::new(&[]);
@@ -0,0 +1,227 @@
+use super::RawLock;
+use std::{
+    cell::UnsafeCell,
+    hint, ptr,
+    sync::atomic::{AtomicPtr, Ordering::*},
+    thread,
+    time::Instant,
+};
+
+/// Очередь CLH: ожидающий крутится на узле предшественника,
+/// а владелец отпускает блокировку, отмечая свой узел.
+///
+/// В отличие от [`Mcs`](super::Mcs) владельцу не нужно искать следующего,
+/// зато каждый ждущий опрашивает чужую память
+pub struct Clh {
+    tail: AtomicPtr<Node>,
+    // узел текущего владельца, его трогает только владелец
+    owner: UnsafeCell<*mut Node>,
+}
+
+unsafe impl Send for Clh {}
+unsafe impl Sync for Clh {}
+
+/// Состояние узла: null, пока владелец узла ждёт или держит блокировку,
+/// [`released`] после `unlock`, а у ушедшего по таймауту это его предшественник
+struct Node {
+    state: AtomicPtr<Node>,
+}
+
+/// Отпущенный узел, с которого начинается очередь каждой блокировки.
+/// Он общий, поэтому его никогда не освобождают. Пока он в хвосте,
+/// блокировка свободна
+static RELEASED: Node = Node {
+    state: AtomicPtr::new(released()),
+};
+
+/// Отметка отпущенного узла, которая не совпадает ни с одним узлом
+const fn released() -> *mut Node {
+    ptr::dangling_mut()
+}
+
+/// Сколько раз ожидающий проверяет узел, прежде чем уступить процессор
+const SPIN: u32 = 100;
+
+impl Clh {
+    fn free(node: *mut Node) {
+        if !ptr::eq(node, &RELEASED) {
+            drop(unsafe { Box::from_raw(node) });
+        }
+    }
+    /// Встаёт в хвост и ждёт отпущенного предшественника до `deadline`.
+    ///
+    /// Ушедший по таймауту узел освобождает следующий за ним,
+    /// переходя к его предшественнику (вариант Скотта)
+    fn acquire(&self, deadline: Option<Instant>) -> bool {
+        let node = Box::into_raw(Box::new(Node {
+            state: AtomicPtr::new(ptr::null_mut()),
+        }));
+        let mut pred = self.tail.swap(node, AcqRel);
+        let mut spins = 0;
+        loop {
+            let state = unsafe { (*pred).state.load(Acquire) };
+            if state == released() {
+                // кроме нас узел предшественника никто больше не читает
+                Self::free(pred);
+                unsafe { *self.owner.get() = node };
+                return true;
+            }
+            if !state.is_null() {
+                // предшественник ушёл, ждём за его предшественником
+                Self::free(pred);
+                pred = state;
+                continue;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                // за нами никого, значит можно просто вернуть хвост
+                if self
+                    .tail
+                    .compare_exchange(node, pred, Release, Relaxed)
+                    .is_ok()
+                {
+                    drop(unsafe { Box::from_raw(node) });
+                } else {
+                    unsafe { (*node).state.store(pred, Release) };
+                }
+                return false;
+            }
+            if spins < SPIN {
+                spins += 1;
+                hint::spin_loop();
+            } else {
+                thread::yield_now();
+            }
+        }
+    }
+}
+
+unsafe impl RawLock for Clh {
+    const INIT: Self = Self {
+        tail: AtomicPtr::new(ptr::addr_of!(RELEASED).cast_mut()),
+        owner: UnsafeCell::new(ptr::null_mut()),
+    };
+
+    fn lock(&self) {
+        self.acquire(None);
+    }
+    /// Встаёт в хвост, только если там `RELEASED`: чужой узел читать нельзя,
+    /// его может освободить следующий. Поэтому сразу после ухода ожидающего
+    /// по таймауту свободная блокировка может не взяться, пока её не возьмёт `lock`
+    fn try_lock(&self) -> bool {
+        let released = ptr::addr_of!(RELEASED).cast_mut();
+        if self.tail.load(Relaxed) != released {
+            return false;
+        }
+        let node = Box::into_raw(Box::new(Node {
+            state: AtomicPtr::new(ptr::null_mut()),
+        }));
+        if self
+            .tail
+            .compare_exchange(released, node, Acquire, Relaxed)
+            .is_err()
+        {
+            drop(unsafe { Box::from_raw(node) });
+            return false;
+        }
+        unsafe { *self.owner.get() = node };
+        true
+    }
+    fn lock_until(&self, deadline: Instant) -> bool {
+        self.acquire(Some(deadline))
+    }
+    unsafe fn unlock(&self) {
+        let node = unsafe { *self.owner.get() };
+        // за нами никого, значит хвост можно вернуть общему узлу
+        if self
+            .tail
+            .compare_exchange(node, ptr::addr_of!(RELEASED).cast_mut(), Release, Relaxed)
+            .is_ok()
+        {
+            drop(unsafe { Box::from_raw(node) });
+            return;
+        }
+        // узел освободит следующий
+        unsafe { (*node).state.store(released(), Release) };
+    }
+}
+
+impl Drop for Clh {
+    fn drop(&mut self) {
+        // в хвосте может остаться цепочка ушедших, которую некому было забрать
+        let mut node = *self.tail.get_mut();
+        while !ptr::eq(node, &RELEASED) {
+            let state = unsafe { *(*node).state.get_mut() };
+            Self::free(node);
+            if state == released() {
+                break;
+            }
+            node = state;
+        }
+    }
+}
+
+#[test]
+fn fifo_handoff() {
+    let lock = super::ClhLock::new(Vec::new());
+    let guard = lock.lock();
+    // каждый следующий встаёт в хвост после предыдущего
+    let tail = || lock.raw.tail.load(Acquire);
+    thread::scope(|s| {
+        for i in 0..8 {
+            let lock = &lock;
+            let before = tail();
+            s.spawn(move || lock.lock().push(i));
+            while tail() == before {
+                thread::yield_now();
+            }
+        }
+        drop(guard);
+    });
+    assert_eq!(lock.into_inner(), (0..8).collect::<Vec<_>>());
+}
+
+#[test]
+fn abandoned_waiter_is_skipped() {
+    use std::time::Duration;
+
+    let lock = super::ClhLock::new(Vec::new());
+    let guard = lock.lock();
+    let tail = || lock.raw.tail.load(Acquire);
+    thread::scope(|s| {
+        let before = tail();
+        s.spawn(|| lock.lock().push(0));
+        while tail() == before {
+            thread::yield_now();
+        }
+        let before = tail();
+        let timed = s.spawn(|| lock.lock_timeout(Duration::from_millis(20)).is_none());
+        while tail() == before {
+            thread::yield_now();
+        }
+        let before = tail();
+        s.spawn(|| lock.lock().push(2));
+        while tail() == before {
+            thread::yield_now();
+        }
+        // за ушедшим стоят, поэтому его узел остаётся в очереди
+        assert!(timed.join().unwrap());
+        drop(guard);
+    });
+    assert_eq!(lock.into_inner(), [0, 2]);
+}
+
+#[test]
+fn try_lock_does_not_enqueue() {
+    let lock = super::ClhLock::new(0);
+    let guard = lock.lock();
+    let tail = lock.raw.tail.load(Acquire);
+    thread::scope(|s| {
+        s.spawn(|| assert!(lock.try_lock().is_none()));
+    });
+    assert_eq!(lock.raw.tail.load(Acquire), tail);
+    drop(guard);
+    // отпущенная без очереди блокировка возвращает хвост общему узлу
+    assert!(ptr::eq(lock.raw.tail.load(Acquire), &RELEASED));
+    *lock.try_lock().unwrap() += 1;
+    assert_eq!(lock.into_inner(), 1);
+}
@@ -0,0 +1,276 @@
+use super::RawLock;
+use std::{
+    cell::{RefCell, UnsafeCell},
+    hint, ptr,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering::*},
+    thread::{self, Thread},
+    time::Instant,
+};
+
+/// Очередь MCS: каждый ожидающий ждёт на своём узле,
+/// а владелец передаёт блокировку следующему в порядке прихода
+pub struct Mcs {
+    // последний в очереди, null если блокировка свободна
+    tail: AtomicPtr<Node>,
+    // узел текущего владельца, его трогает только владелец
+    owner: UnsafeCell<*mut Node>,
+}
+
+unsafe impl Send for Mcs {}
+unsafe impl Sync for Mcs {}
+
+struct Node {
+    next: AtomicPtr<Node>,
+    status: AtomicU8,
+    thread: Thread,
+}
+
+const WAITING: u8 = 0;
+const GRANTED: u8 = 1;
+/// Ожидающий ушёл по таймауту, и его узел забирает тот, кто передаёт блокировку
+const ABANDONED: u8 = 2;
+
+/// Сколько раз ожидающий проверяет свой узел, прежде чем заснуть
+const SPIN: u32 = 100;
+
+/// Освободившиеся узлы потока, чтобы не выделять узел на каждый захват
+struct NodePool(Vec<*mut Node>);
+
+impl Drop for NodePool {
+    fn drop(&mut self) {
+        for node in self.0.drain(..) {
+            drop(unsafe { Box::from_raw(node) });
+        }
+    }
+}
+
+thread_local! {
+    static NODES: RefCell<NodePool> = const { RefCell::new(NodePool(Vec::new())) };
+}
+
+unsafe impl RawLock for Mcs {
+    const INIT: Self = Self {
+        tail: AtomicPtr::new(ptr::null_mut()),
+        owner: UnsafeCell::new(ptr::null_mut()),
+    };
+
+    fn lock(&self) {
+        self.acquire(None);
+    }
+    fn try_lock(&self) -> bool {
+        if !self.tail.load(Relaxed).is_null() {
+            return false;
+        }
+        let node = Self::node();
+        if self
+            .tail
+            .compare_exchange(ptr::null_mut(), node, Acquire, Relaxed)
+            .is_err()
+        {
+            unsafe { Self::free(node) };
+            return false;
+        }
+        unsafe { *self.owner.get() = node };
+        true
+    }
+    fn lock_until(&self, deadline: Instant) -> bool {
+        self.acquire(Some(deadline))
+    }
+    /// Передаёт блокировку следующему или освобождает её, если очередь пуста.
+    /// Ушедших по таймауту пропускает
+    unsafe fn unlock(&self) {
+        let mut node = unsafe { *self.owner.get() };
+        loop {
+            let mut next = unsafe { (*node).next.load(Acquire) };
+            if next.is_null() {
+                if self
+                    .tail
+                    .compare_exchange(node, ptr::null_mut(), Release, Relaxed)
+                    .is_ok()
+                {
+                    unsafe { Self::free(node) };
+                    return;
+                }
+                // следующий уже встал в хвост, но ещё не прицепился к нам
+                while {
+                    next = unsafe { (*node).next.load(Acquire) };
+                    next.is_null()
+                } {
+                    hint::spin_loop();
+                }
+            }
+            unsafe { Self::free(node) };
+            let next_ref = unsafe { &*next };
+            // после передачи узел следующего может исчезнуть
+            let thread = next_ref.thread.clone();
+            if next_ref
+                .status
+                .compare_exchange(WAITING, GRANTED, Release, Relaxed)
+                .is_ok()
+            {
+                thread.unpark();
+                return;
+            }
+            // следующий ушёл, и его узел теперь наш
+            node = next;
+        }
+    }
+}
+
+impl Mcs {
+    /// Берёт узел из пула потока или выделяет новый
+    fn node() -> *mut Node {
+        let node = NODES
+            .try_with(|nodes| nodes.borrow_mut().0.pop())
+            .ok()
+            .flatten();
+        match node {
+            Some(node) => {
+                let node_ref = unsafe { &*node };
+                node_ref.next.store(ptr::null_mut(), Relaxed);
+                node_ref.status.store(WAITING, Relaxed);
+                node
+            }
+            None => Box::into_raw(Box::new(Node {
+                next: AtomicPtr::new(ptr::null_mut()),
+                status: AtomicU8::new(WAITING),
+                thread: thread::current(),
+            })),
+        }
+    }
+    /// Возвращает узел в пул, если он принадлежит текущему потоку.
+    /// Узлы ушедших по таймауту освобождает чужой поток, они удаляются
+    ///
+    /// # Safety
+    /// Узел больше не используется ни одним потоком
+    unsafe fn free(node: *mut Node) {
+        if unsafe { &*node }.thread.id() == thread::current().id()
+            && NODES
+                .try_with(|nodes| nodes.borrow_mut().0.push(node))
+                .is_ok()
+        {
+            return;
+        }
+        drop(unsafe { Box::from_raw(node) });
+    }
+    /// Встаёт в конец очереди и ждёт передачи до `deadline`.
+    /// Уходя по таймауту, оставляет узел в очереди помеченным
+    fn acquire(&self, deadline: Option<Instant>) -> bool {
+        let node = Self::node();
+        let prev = self.tail.swap(node, AcqRel);
+        if !prev.is_null() {
+            // предыдущий не освободит свой узел, пока не передаст блокировку нам
+            unsafe { (*prev).next.store(node, Release) };
+            let node = unsafe { &*node };
+            for _ in 0..SPIN {
+                if node.status.load(Acquire) != WAITING {
+                    break;
+                }
+                hint::spin_loop();
+            }
+            // park может проснуться ложно, поэтому проверяем свой узел снова
+            while node.status.load(Acquire) == WAITING {
+                let Some(deadline) = deadline else {
+                    thread::park();
+                    continue;
+                };
+                let now = Instant::now();
+                if now < deadline {
+                    thread::park_timeout(deadline - now);
+                    continue;
+                }
+                // не успели: блокировку либо уже передали нам, либо узел больше не наш
+                if node
+                    .status
+                    .compare_exchange(WAITING, ABANDONED, Relaxed, Acquire)
+                    .is_ok()
+                {
+                    return false;
+                }
+            }
+        }
+        unsafe { *self.owner.get() = node };
+        true
+    }
+}
+
+#[test]
+fn fifo_handoff() {
+    let lock = super::QueueLock::new(Vec::new());
+    let guard = lock.lock();
+    // пока блокировка у нас, узлы очереди никто не освобождает
+    let queued = || {
+        let mut node = unsafe { *lock.raw.owner.get() };
+        let mut len = 0;
+        while let Some(next) = unsafe { (*node).next.load(Acquire).as_mut() } {
+            node = next;
+            len += 1;
+        }
+        len
+    };
+    thread::scope(|s| {
+        for i in 0..8 {
+            let lock = &lock;
+            s.spawn(move || lock.lock().push(i));
+            // следующий встаёт в очередь только после предыдущего
+            while queued() != i + 1 {
+                thread::yield_now();
+            }
+        }
+        drop(guard);
+    });
+    assert_eq!(lock.into_inner(), (0..8).collect::<Vec<_>>());
+}
+
+#[test]
+fn abandoned_waiter_is_skipped() {
+    use std::time::Duration;
+
+    let lock = super::QueueLock::new(Vec::new());
+    let guard = lock.lock();
+    let queued = || {
+        let mut node = unsafe { *lock.raw.owner.get() };
+        let mut len = 0;
+        while let Some(next) = unsafe { (*node).next.load(Acquire).as_mut() } {
+            node = next;
+            len += 1;
+        }
+        len
+    };
+    thread::scope(|s| {
+        s.spawn(|| lock.lock().push(0));
+        while queued() != 1 {
+            thread::yield_now();
+        }
+        let timed = s.spawn(|| lock.lock_timeout(Duration::from_millis(20)).is_none());
+        while queued() != 2 {
+            thread::yield_now();
+        }
+        s.spawn(|| lock.lock().push(2));
+        while queued() != 3 {
+            thread::yield_now();
+        }
+        // ушедший остаётся в очереди, но блокировку через него не передают
+        assert!(timed.join().unwrap());
+        drop(guard);
+    });
+    assert_eq!(lock.into_inner(), [0, 2]);
+}
+
+#[test]
+fn nodes_are_reused() {
+    let lock = super::QueueLock::new(0);
+    thread::spawn(move || {
+        for _ in 0..1_000 {
+            *lock.lock() += 1;
+            if let Some(mut guard) = lock.try_lock() {
+                *guard += 1;
+            }
+        }
+        // один узел ходит по кругу
+        assert_eq!(NODES.with(|nodes| nodes.borrow().0.len()), 1);
+        assert_eq!(lock.into_inner(), 2_000);
+    })
+    .join()
+    .unwrap();
+}
@@ -1,118 +1,321 @@
-use crate::rcu::Rcu;
-use rand::Rng;
 use std::{
     cell::UnsafeCell,
-    collections::VecDeque,
     ops::{Deref, DerefMut},
-    sync::{
-        atomic::{AtomicBool, AtomicPtr, Ordering::*},
-        Arc, Mutex,
-    },
-    thread::{self, Thread},
+    thread,
     time::{Duration, Instant},
 };
-use std_reset::{prelude::Default, traits::as_prim::AsPrim};
 
-struct QueueLock<T> {
-    queue: Rcu<VecDeque<Thread>>,
-    is_busy: AtomicBool,
+pub mod clh;
+pub mod mcs;
+pub mod poison;
+#[cfg(feature = "lock_api")]
+pub mod raw_mutex;
+pub mod ticket;
+pub mod ttas;
+
+pub use clh::Clh;
+pub use mcs::Mcs;
+pub use poison::{PoisonGuard, PoisonLock};
+#[cfg(feature = "lock_api")]
+pub use raw_mutex::QueueMutex;
+pub use ticket::Ticket;
+pub use ttas::Ttas;
+
+/// Алгоритм взаимного исключения без данных, который подставляется в [`Lock`]
+/// и [`PoisonLock`]. С фичей `lock_api` алгоритмы этого модуля также
+/// реализуют `lock_api::RawMutex`, новый нужно добавить в список в `raw_mutex`
+///
+/// # Safety
+/// После `lock` и до `unlock` никакой другой `lock` не должен вернуться
+pub unsafe trait RawLock {
+    /// Свободная блокировка
+    const INIT: Self;
+
+    /// Возвращается, только став владельцем
+    fn lock(&self);
+    /// Становится владельцем, только если не придётся ждать
+    fn try_lock(&self) -> bool;
+    /// Ждёт не дольше `deadline` и возвращает `false`, не дождавшись.
+    /// Без своей реализации просто опрашивает [`try_lock`](Self::try_lock),
+    /// не вставая в очередь, поэтому может голодать за ожидающими в [`lock`](Self::lock)
+    fn lock_until(&self, deadline: Instant) -> bool {
+        while !self.try_lock() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::yield_now();
+        }
+        true
+    }
+    /// # Safety
+    /// Вызывает только текущий владелец
+    unsafe fn unlock(&self);
+}
+
+/// Данные под блокировкой с алгоритмом `R`
+pub struct Lock<R, T> {
+    raw: R,
     data: UnsafeCell<T>,
 }
 
-unsafe impl<T: Send> Sync for QueueLock<T> {}
+unsafe impl<R: Send, T: Send> Send for Lock<R, T> {}
+unsafe impl<R: Sync, T: Send> Sync for Lock<R, T> {}
+
+/// Очередь MCS с передачей владения в порядке прихода
+pub type QueueLock<T> = Lock<Mcs, T>;
+/// Очередь CLH с ожиданием на узле предшественника
+pub type ClhLock<T> = Lock<Clh, T>;
+/// Билетная блокировка, порядок прихода без очереди в куче
+pub type TicketLock<T> = Lock<Ticket, T>;
+/// Спин-блокировка для самых коротких критических секций
+pub type SpinLock<T> = Lock<Ttas, T>;
+/// [`QueueLock`] с отравлением при панике владельца
+pub type PoisonQueueLock<T> = PoisonLock<Mcs, T>;
 
-impl<T: Default> QueueLock<T> {
-    pub fn new(data: T) -> Self {
+impl<R: RawLock, T> Lock<R, T> {
+    pub const fn new(data: T) -> Self {
         Self {
-            queue: Rcu::new(VecDeque::new()),
+            raw: R::INIT,
             data: UnsafeCell::new(data),
-            is_busy: AtomicBool::new(false),
         }
     }
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+    pub fn lock(&self) -> Guard<'_, R, T> {
+        self.raw.lock();
+        Guard { lock: self }
+    }
+    pub fn try_lock(&self) -> Option<Guard<'_, R, T>> {
+        self.raw.try_lock().then(|| Guard { lock: self })
+    }
+    /// Ушедший по таймауту покидает очередь, не задерживая стоящих за ним.
+    /// Очередь с таймаутом есть только у [`Mcs`] и [`Clh`], а у [`Ticket`] и [`Ttas`]
+    /// ожидание с таймаутом не встаёт в очередь и справедливым не бывает
+    pub fn lock_timeout(&self, timeout: Duration) -> Option<Guard<'_, R, T>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.raw.lock_until(deadline).then(|| Guard { lock: self }),
+            None => Some(self.lock()),
+        }
+    }
+}
+
+impl<R: RawLock, T: Default> Default for Lock<R, T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
 }
 
-struct Guard<'a, T> {
-    queue_lock: &'a QueueLock<T>,
+/// Владение блокировкой, которое отпускается при удалении
+#[must_use]
+pub struct Guard<'a, R: RawLock, T> {
+    lock: &'a Lock<R, T>,
 }
-impl<T> Deref for Guard<'_, T> {
+
+impl<R: RawLock, T> Deref for Guard<'_, R, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        unsafe { &*self.queue_lock.data.get() }
+        unsafe { &*self.lock.data.get() }
     }
 }
 
-impl<T> DerefMut for Guard<'_, T> {
+impl<R: RawLock, T> DerefMut for Guard<'_, R, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { &mut *self.queue_lock.data.get() }
+        unsafe { &mut *self.lock.data.get() }
     }
 }
 
-impl<T> Drop for Guard<'_, T> {
+impl<R: RawLock, T> Drop for Guard<'_, R, T> {
     fn drop(&mut self) {
-        self.queue_lock.is_busy.store(false, Release);
-        self.queue_lock.queue.change(|queue| {
-            queue.pop_front().map(|t| t.unpark());
-        });
+        unsafe { self.lock.raw.unlock() };
     }
 }
-impl<T> QueueLock<T> {
-    pub fn lock(&self) -> Guard<T> {
-        while self
-            .is_busy
-            .compare_exchange(false, true, Relaxed, Relaxed)
-            .is_err()
-        {
-            self.queue.change(|queue| {
-                queue.push_back(thread::current());
+
+/// Нагрузка из [`test`]: каждый из 10 потоков запускает `threads` потоков,
+/// и каждый из них выполняет `critical_section`
+#[cfg(test)]
+fn spawners(threads: usize, critical_section: impl Fn() + Sync) {
+    thread::scope(|s| {
+        for _ in 0..10 {
+            s.spawn(|| {
+                thread::scope(|s| {
+                    // ждём явно: pthread_detach в glibc может прочитать стек завершившегося потока
+                    let handles = (0..threads)
+                        .map(|_| s.spawn(&critical_section))
+                        .collect::<Vec<_>>();
+                    handles.into_iter().for_each(|h| h.join().unwrap());
+                });
             });
-            if self.is_busy.load(Relaxed) {
-                thread::park();
-                break;
-            }
         }
-        Guard { queue_lock: self }
-    }
-    pub fn unlock(&self) {
-        self.is_busy.store(false, Release);
-        self.queue.change(|queue| {
-            let t = queue.pop_front();
-            if let Some(t) = t {
-                t.unpark();
-            };
-        });
-    }
+    });
 }
 
 #[test]
 fn test() {
     let queue = QueueLock::new(0);
+    // волнами по 10 000 потоков, чтобы не упереться в vm.max_map_count
+    for _ in 0..3 {
+        spawners(1_000, || *queue.lock() += 1);
+    }
+    assert_eq!(queue.into_inner(), 30_000);
+}
 
-    let times = Mutex::new(Vec::new());
+#[cfg(test)]
+fn mutual_exclusion<R: RawLock + Sync>() {
+    let lock = Lock::<R, _>::new((0u64, 0u64));
     thread::scope(|s| {
-        for _ in 0..10 {
+        for _ in 0..8 {
             s.spawn(|| {
-                let start = Instant::now();
-                thread::scope(|s| {
-                    for _ in 0..3_000 {
-                        s.spawn({
-                            || {
-                                queue.lock();
-                            }
-                        });
-                    }
-                });
-                times.lock().unwrap().push(start.elapsed());
+                for _ in 0..10_000 {
+                    let mut data = lock.lock();
+                    // второй поток внутри увидел бы пару наполовину изменённой
+                    assert_eq!(data.0, data.1);
+                    data.0 += 1;
+                    data.1 += 1;
+                }
             });
         }
     });
-    let times = times.into_inner().unwrap();
-
-    let t = times
-        .iter()
-        .cloned()
-        .reduce(|a, b| a + b)
-        .unwrap()
-        .as_micros()
-        / times.len().as_::<u128>();
-    dbg!(Duration::from_micros(t.as_()));
+    assert_eq!(lock.into_inner(), (80_000, 80_000));
+}
+
+#[test]
+fn mcs_mutual_exclusion() {
+    mutual_exclusion::<Mcs>();
+}
+
+#[test]
+fn clh_mutual_exclusion() {
+    mutual_exclusion::<Clh>();
+}
+
+#[test]
+fn ticket_mutual_exclusion() {
+    mutual_exclusion::<Ticket>();
+}
+
+#[test]
+fn ttas_mutual_exclusion() {
+    mutual_exclusion::<Ttas>();
+}
+
+#[cfg(test)]
+fn try_lock_and_timeout<R: RawLock + Sync>() {
+    let lock = Lock::<R, _>::new(0);
+    let guard = lock.lock();
+    thread::scope(|s| {
+        s.spawn(|| {
+            assert!(lock.try_lock().is_none());
+            let start = Instant::now();
+            assert!(lock.lock_timeout(Duration::from_millis(20)).is_none());
+            assert!(start.elapsed() >= Duration::from_millis(20));
+        });
+    });
+    drop(guard);
+    *lock.try_lock().unwrap() += 1;
+    *lock.lock_timeout(Duration::ZERO).unwrap() += 1;
+    // срок за пределами Instant означает обычное ожидание
+    *lock.lock_timeout(Duration::MAX).unwrap() += 1;
+    assert_eq!(*lock.lock(), 3);
+}
+
+/// Часть потоков бросает ожидание на полпути, остальные должны
+/// по-прежнему входить по одному и не застревать за ушедшими
+#[cfg(test)]
+fn abandonment<R: RawLock + Sync>() {
+    use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+    let lock = Lock::<R, _>::new((0u64, 0u64));
+    let entered = AtomicU64::new(0);
+    thread::scope(|s| {
+        for i in 0..8 {
+            let (lock, entered) = (&lock, &entered);
+            s.spawn(move || {
+                for j in 0..2_000u64 {
+                    let guard = match (i + j) % 4 {
+                        0 => Some(lock.lock()),
+                        1 => lock.try_lock(),
+                        _ => lock.lock_timeout(Duration::from_micros(j % 50)),
+                    };
+                    let Some(mut data) = guard else { continue };
+                    assert_eq!(data.0, data.1);
+                    data.0 += 1;
+                    // держим подольше, чтобы ожидания истекали в очереди
+                    thread::yield_now();
+                    data.1 += 1;
+                    entered.fetch_add(1, Relaxed);
+                }
+            });
+        }
+    });
+    let entered = entered.into_inner();
+    assert!(entered >= 8 * 500);
+    assert_eq!(lock.into_inner(), (entered, entered));
+}
+
+#[test]
+fn mcs_timeout() {
+    try_lock_and_timeout::<Mcs>();
+    abandonment::<Mcs>();
+}
+
+#[test]
+fn clh_timeout() {
+    try_lock_and_timeout::<Clh>();
+    abandonment::<Clh>();
+}
+
+#[test]
+fn ticket_timeout() {
+    try_lock_and_timeout::<Ticket>();
+    abandonment::<Ticket>();
+}
+
+#[test]
+fn ttas_timeout() {
+    try_lock_and_timeout::<Ttas>();
+    abandonment::<Ttas>();
+}
+
+#[cfg(test)]
+fn bench<R: RawLock + Sync>(b: &mut test::Bencher, threads: usize) {
+    let lock = Lock::<R, _>::new(0);
+    b.iter(|| spawners(threads, || (0..10).for_each(|_| *lock.lock() += 1)));
+}
+
+/// Сравнение алгоритмов с `std::sync::Mutex` на нагрузке из [`test`]
+/// при разном числе потоков у каждого из 10 запускающих
+macro_rules! compare {
+    ($($threads:literal: $mcs:ident, $clh:ident, $ticket:ident, $ttas:ident, $mutex:ident;)*) => {$(
+        #[bench]
+        fn $mcs(b: &mut test::Bencher) {
+            bench::<Mcs>(b, $threads);
+        }
+        #[bench]
+        fn $clh(b: &mut test::Bencher) {
+            bench::<Clh>(b, $threads);
+        }
+        #[bench]
+        fn $ticket(b: &mut test::Bencher) {
+            bench::<Ticket>(b, $threads);
+        }
+        #[bench]
+        fn $ttas(b: &mut test::Bencher) {
+            bench::<Ttas>(b, $threads);
+        }
+        #[bench]
+        fn $mutex(b: &mut test::Bencher) {
+            let mutex = std::sync::Mutex::new(0);
+            b.iter(|| spawners($threads, || (0..10).for_each(|_| *mutex.lock().unwrap() += 1)));
+        }
+    )*};
+}
+
+compare! {
+    1: mcs_10, clh_10, ticket_10, ttas_10, mutex_10;
+    10: mcs_100, clh_100, ticket_100, ttas_100, mutex_100;
+    100: mcs_1000, clh_1000, ticket_1000, ttas_1000, mutex_1000;
 }
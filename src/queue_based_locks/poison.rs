@@ -0,0 +1,165 @@
+use super::{Guard, Lock, RawLock};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering::*},
+        LockResult, PoisonError, TryLockError, TryLockResult,
+    },
+    thread,
+    time::Duration,
+};
+
+/// [`Lock`] с отравлением как у `std::sync::Mutex`: если владелец запаниковал,
+/// следующие получат данные внутри [`PoisonError`].
+///
+/// Отдельный тип, чтобы обычный [`Lock`] не платил за проверку паники
+pub struct PoisonLock<R, T> {
+    lock: Lock<R, T>,
+    poisoned: AtomicBool,
+}
+
+impl<R: RawLock, T> PoisonLock<R, T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            lock: Lock::new(data),
+            poisoned: AtomicBool::new(false),
+        }
+    }
+    pub fn into_inner(self) -> LockResult<T> {
+        let poisoned = self.is_poisoned();
+        Self::result(poisoned, self.lock.into_inner())
+    }
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let poisoned = self.is_poisoned();
+        Self::result(poisoned, self.lock.get_mut())
+    }
+    pub fn lock(&self) -> LockResult<PoisonGuard<'_, R, T>> {
+        self.guard(self.lock.lock())
+    }
+    pub fn try_lock(&self) -> TryLockResult<PoisonGuard<'_, R, T>> {
+        let guard = self.lock.try_lock().ok_or(TryLockError::WouldBlock)?;
+        Ok(self.guard(guard)?)
+    }
+    /// По истечении `timeout` возвращает [`TryLockError::WouldBlock`]
+    pub fn lock_timeout(&self, timeout: Duration) -> TryLockResult<PoisonGuard<'_, R, T>> {
+        let guard = self
+            .lock
+            .lock_timeout(timeout)
+            .ok_or(TryLockError::WouldBlock)?;
+        Ok(self.guard(guard)?)
+    }
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Relaxed)
+    }
+    /// Снимает отравление, когда данные приведены в порядок
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Relaxed);
+    }
+    fn guard<'a>(&'a self, guard: Guard<'a, R, T>) -> LockResult<PoisonGuard<'a, R, T>> {
+        let guard = PoisonGuard {
+            guard,
+            poisoned: &self.poisoned,
+            // паника, начатая до захвата, не говорит о порче данных
+            panicking: thread::panicking(),
+        };
+        Self::result(self.is_poisoned(), guard)
+    }
+    fn result<G>(poisoned: bool, guard: G) -> LockResult<G> {
+        if poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+impl<R: RawLock, T: Default> Default for PoisonLock<R, T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// Владение [`PoisonLock`], отравляющее её, если поток паникует с ним в руках
+#[must_use]
+pub struct PoisonGuard<'a, R: RawLock, T> {
+    guard: Guard<'a, R, T>,
+    poisoned: &'a AtomicBool,
+    panicking: bool,
+}
+
+impl<R: RawLock, T> Deref for PoisonGuard<'_, R, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<R: RawLock, T> DerefMut for PoisonGuard<'_, R, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl<R: RawLock, T> Drop for PoisonGuard<'_, R, T> {
+    fn drop(&mut self) {
+        // флаг ставится до отпускания, поэтому следующий владелец его увидит
+        if !self.panicking && thread::panicking() {
+            self.poisoned.store(true, Relaxed);
+        }
+    }
+}
+
+#[test]
+fn panic_poisons() {
+    use super::Mcs;
+
+    let lock = PoisonLock::<Mcs, _>::new(vec![1]);
+    let result = thread::scope(|s| {
+        s.spawn(|| {
+            let mut data = lock.lock().unwrap();
+            data.push(2);
+            panic!("данные наполовину изменены");
+        })
+        .join()
+    });
+    assert!(result.is_err());
+    assert!(lock.is_poisoned());
+
+    // данные всё равно доступны
+    let Err(poisoned) = lock.lock() else {
+        unreachable!()
+    };
+    let data = poisoned.into_inner();
+    assert_eq!(*data, [1, 2]);
+    drop(data);
+    assert!(matches!(lock.try_lock(), Err(TryLockError::Poisoned(_))));
+
+    lock.clear_poison();
+    lock.lock().unwrap().pop();
+    assert_eq!(lock.into_inner().unwrap(), [1]);
+}
+
+#[test]
+fn panic_without_guard_does_not_poison() {
+    use super::Ttas;
+
+    let lock = PoisonLock::<Ttas, _>::new(0);
+    let result = thread::scope(|s| {
+        s.spawn(|| {
+            *lock.lock().unwrap() += 1;
+            panic!("блокировка уже отпущена");
+        })
+        .join()
+    });
+    assert!(result.is_err());
+    assert!(!lock.is_poisoned());
+
+    let guard = lock.lock().unwrap();
+    assert!(matches!(lock.try_lock(), Err(TryLockError::WouldBlock)));
+    assert!(matches!(
+        lock.lock_timeout(Duration::from_millis(1)),
+        Err(TryLockError::WouldBlock)
+    ));
+    drop(guard);
+    assert_eq!(*lock.lock_timeout(Duration::ZERO).unwrap(), 1);
+}
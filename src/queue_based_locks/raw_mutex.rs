@@ -0,0 +1,91 @@
+//! Алгоритмы из этого модуля как [`lock_api::RawMutex`], включается фичей `lock_api`
+use super::{Clh, Mcs, RawLock, Ticket, Ttas};
+use lock_api::{GuardSend, RawMutex, RawMutexTimed};
+use std::time::{Duration, Instant};
+
+/// `lock_api::Mutex` с очередью MCS
+pub type QueueMutex<T> = lock_api::Mutex<Mcs, T>;
+
+// свой trait для чужих типов-параметров реализовать нельзя, поэтому по одному
+macro_rules! raw_mutex {
+    ($($raw:ty),*) => {$(
+        unsafe impl RawMutex for $raw {
+            const INIT: Self = <Self as RawLock>::INIT;
+            // владелец хранится в самой блокировке, отпустить можно из любого потока
+            type GuardMarker = GuardSend;
+
+            fn lock(&self) {
+                RawLock::lock(self)
+            }
+            fn try_lock(&self) -> bool {
+                RawLock::try_lock(self)
+            }
+            unsafe fn unlock(&self) {
+                unsafe { RawLock::unlock(self) }
+            }
+        }
+
+        unsafe impl RawMutexTimed for $raw {
+            type Duration = Duration;
+            type Instant = Instant;
+
+            fn try_lock_for(&self, timeout: Duration) -> bool {
+                match Instant::now().checked_add(timeout) {
+                    Some(deadline) => self.lock_until(deadline),
+                    None => {
+                        RawLock::lock(self);
+                        true
+                    }
+                }
+            }
+            fn try_lock_until(&self, deadline: Instant) -> bool {
+                self.lock_until(deadline)
+            }
+        }
+    )*};
+}
+
+raw_mutex!(Mcs, Clh, Ticket, Ttas);
+
+#[cfg(test)]
+fn mutex<R: RawMutexTimed<Duration = Duration> + Sync>() {
+    use std::thread;
+
+    let mutex = lock_api::Mutex::<R, _>::new(0);
+    thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                for _ in 0..1_000 {
+                    *mutex.lock() += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(*mutex.lock(), 8_000);
+
+    let guard = mutex.lock();
+    assert!(mutex.is_locked());
+    thread::scope(|s| {
+        s.spawn(|| {
+            assert!(mutex.try_lock().is_none());
+            assert!(mutex.try_lock_for(Duration::from_millis(5)).is_none());
+        });
+    });
+    drop(guard);
+    assert!(!mutex.is_locked());
+    assert!(mutex.try_lock_for(Duration::ZERO).is_some());
+    assert!(mutex.try_lock_for(Duration::MAX).is_some());
+}
+
+#[test]
+fn lock_api_mutex() {
+    mutex::<Mcs>();
+    mutex::<Clh>();
+    mutex::<Ticket>();
+    mutex::<Ttas>();
+
+    // статическая инициализация, ради которой у RawMutex есть INIT
+    static COUNTER: QueueMutex<u32> = QueueMutex::const_new(RawMutex::INIT, 0);
+    *COUNTER.lock() += 1;
+    assert_eq!(*COUNTER.lock(), 1);
+}
@@ -0,0 +1,76 @@
+use super::RawLock;
+use std::{
+    hint,
+    sync::atomic::{AtomicU32, Ordering::*},
+    thread,
+};
+
+/// Билетная блокировка: каждый берёт номер и ждёт, пока его не вызовут.
+///
+/// Порядок прихода соблюдается, как в [`Mcs`](super::Mcs), но без узлов в куче.
+/// Все ожидающие опрашивают один счётчик, поэтому при многих потоках она проигрывает очередям.
+/// Взятый билет вернуть нельзя, поэтому `lock_until` опрашивает `try_lock` без билета:
+/// ожидание с таймаутом несправедливо и может голодать за теми, кто ждёт в `lock`
+pub struct Ticket {
+    next_ticket: AtomicU32,
+    now_serving: AtomicU32,
+}
+
+/// Сколько раз следующий по очереди проверяет счётчик, прежде чем уступить процессор
+const SPIN: u32 = 100;
+
+unsafe impl RawLock for Ticket {
+    const INIT: Self = Self {
+        next_ticket: AtomicU32::new(0),
+        now_serving: AtomicU32::new(0),
+    };
+
+    fn lock(&self) {
+        let ticket = self.next_ticket.fetch_add(1, Relaxed);
+        let mut spins = 0;
+        loop {
+            let ahead = ticket.wrapping_sub(self.now_serving.load(Acquire));
+            if ahead == 0 {
+                return;
+            }
+            // крутиться есть смысл только следующему, остальным ждать ещё долго.
+            // К тому же владелец мог потерять процессор
+            if ahead == 1 && spins < SPIN {
+                spins += 1;
+                hint::spin_loop();
+            } else {
+                thread::yield_now();
+            }
+        }
+    }
+    /// Берёт билет, только если он сразу будет обслужен
+    fn try_lock(&self) -> bool {
+        let serving = self.now_serving.load(Acquire);
+        self.next_ticket
+            .compare_exchange(serving, serving.wrapping_add(1), Acquire, Relaxed)
+            .is_ok()
+    }
+    unsafe fn unlock(&self) {
+        // пишет только владелец, поэтому хватает чтения и записи
+        let serving = self.now_serving.load(Relaxed);
+        self.now_serving.store(serving.wrapping_add(1), Release);
+    }
+}
+
+#[test]
+fn fifo_handoff() {
+    let lock = super::TicketLock::new(Vec::new());
+    let guard = lock.lock();
+    thread::scope(|s| {
+        for i in 0..8 {
+            let lock = &lock;
+            s.spawn(move || lock.lock().push(i));
+            // следующий берёт билет только после предыдущего
+            while lock.raw.next_ticket.load(Relaxed) != i + 2 {
+                thread::yield_now();
+            }
+        }
+        drop(guard);
+    });
+    assert_eq!(lock.into_inner(), (0..8).collect::<Vec<_>>());
+}
@@ -0,0 +1,44 @@
+use super::RawLock;
+use std::{
+    hint,
+    sync::atomic::{AtomicBool, Ordering::*},
+    thread,
+};
+
+/// Спин-блокировка test-and-test-and-set с экспоненциальной паузой.
+///
+/// Ожидающие читают флаг из своего кэша и пробуют его захватить, только увидев,
+/// что он снят. Порядок не соблюдается, зато на коротких
+/// критических секциях вроде счётчиков это самый быстрый вариант
+pub struct Ttas {
+    locked: AtomicBool,
+}
+
+/// Предел паузы между проверками, после него ожидающий уступает процессор
+const MAX_BACKOFF: u32 = 1 << 10;
+
+unsafe impl RawLock for Ttas {
+    const INIT: Self = Self {
+        locked: AtomicBool::new(false),
+    };
+
+    fn lock(&self) {
+        let mut backoff = 1;
+        while self.locked.swap(true, Acquire) {
+            while self.locked.load(Relaxed) {
+                if backoff < MAX_BACKOFF {
+                    (0..backoff).for_each(|_| hint::spin_loop());
+                    backoff *= 2;
+                } else {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+    fn try_lock(&self) -> bool {
+        !self.locked.load(Relaxed) && !self.locked.swap(true, Acquire)
+    }
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Release);
+    }
+}
@@ -0,0 +1,167 @@
+use crossbeam::queue::SegQueue;
+use std::{
+    mem::MaybeUninit,
+    ptr,
+    sync::{
+        atomic::{AtomicUsize, Ordering::*},
+        Arc,
+    },
+};
+
+/// Откуда берётся память под версии данных [`Rcu`](super::Rcu)
+pub trait RcuAlloc<T> {
+    fn alloc(&self, data: T) -> *mut T;
+    /// Удаляет данные и освобождает память под них
+    ///
+    /// # Safety
+    /// `ptr` должен быть получен из [`RcuAlloc::alloc`] этого же аллокатора,
+    /// и больше никем не использоваться
+    unsafe fn dealloc(&self, ptr: *mut T);
+}
+
+/// Глобальный аллокатор, каждая версия это отдельный [`Box`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Global;
+
+impl<T> RcuAlloc<T> for Global {
+    fn alloc(&self, data: T) -> *mut T {
+        Box::into_raw(Box::new(data))
+    }
+    unsafe fn dealloc(&self, ptr: *mut T) {
+        drop(Box::from_raw(ptr));
+    }
+}
+
+struct Slot<T>(*mut MaybeUninit<T>);
+// в слоте лежит только неинициализированная память
+unsafe impl<T> Send for Slot<T> {}
+
+/// Пул памяти под версии данных.
+///
+/// Освобождённые версии не возвращаются в глобальный аллокатор,
+/// а складываются в lock-free список свободных слотов и переиспользуются
+pub struct Pool<T> {
+    free: SegQueue<Slot<T>>,
+    allocated: AtomicUsize,
+}
+
+impl<T> Pool<T> {
+    pub const fn new() -> Self {
+        Self {
+            free: SegQueue::new(),
+            allocated: AtomicUsize::new(0),
+        }
+    }
+    /// Создаёт пул с заранее выделенными `capacity` слотами
+    pub fn with_capacity(capacity: usize) -> Self {
+        let pool = Self::new();
+        for _ in 0..capacity {
+            pool.free.push(pool.new_slot());
+        }
+        pool
+    }
+    /// Количество свободных слотов
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+    /// Сколько всего слотов было взято у глобального аллокатора
+    pub fn allocated(&self) -> usize {
+        self.allocated.load(Relaxed)
+    }
+    fn new_slot(&self) -> Slot<T> {
+        self.allocated.fetch_add(1, Relaxed);
+        Slot(Box::into_raw(Box::new(MaybeUninit::uninit())))
+    }
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::fmt::Debug for Pool<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pool")
+            .field("free", &self.len())
+            .field("allocated", &self.allocated())
+            .finish()
+    }
+}
+
+impl<T> RcuAlloc<T> for Pool<T> {
+    fn alloc(&self, data: T) -> *mut T {
+        let slot = self.free.pop().unwrap_or_else(|| self.new_slot()).0;
+        unsafe { (*slot).write(data) };
+        slot.cast()
+    }
+    unsafe fn dealloc(&self, ptr: *mut T) {
+        ptr::drop_in_place(ptr);
+        self.free.push(Slot(ptr.cast()));
+    }
+}
+
+impl<T> Drop for Pool<T> {
+    fn drop(&mut self) {
+        while let Some(slot) = self.free.pop() {
+            drop(unsafe { Box::from_raw(slot.0) });
+        }
+    }
+}
+
+// один пул может раздаваться нескольким ячейкам
+impl<T, A: RcuAlloc<T> + ?Sized> RcuAlloc<T> for &A {
+    fn alloc(&self, data: T) -> *mut T {
+        (**self).alloc(data)
+    }
+    unsafe fn dealloc(&self, ptr: *mut T) {
+        (**self).dealloc(ptr)
+    }
+}
+
+impl<T, A: RcuAlloc<T> + ?Sized> RcuAlloc<T> for Arc<A> {
+    fn alloc(&self, data: T) -> *mut T {
+        (**self).alloc(data)
+    }
+    unsafe fn dealloc(&self, ptr: *mut T) {
+        (**self).dealloc(ptr)
+    }
+}
+
+#[test]
+fn pool_recycles_slots() {
+    let pool = Pool::with_capacity(2);
+    assert_eq!(pool.allocated(), 2);
+
+    for i in 0..1_000 {
+        let ptr = pool.alloc(String::from("version"));
+        let other = pool.alloc(format!("{i}"));
+        unsafe {
+            pool.dealloc(ptr);
+            pool.dealloc(other);
+        }
+    }
+    assert_eq!(pool.allocated(), 2);
+    assert_eq!(pool.len(), 2);
+}
+
+#[test]
+fn pool_from_threads() {
+    let pool = Pool::new();
+    std::thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                for i in 0..10_000 {
+                    let ptr = pool.alloc(vec![i]);
+                    assert_eq!(unsafe { &*ptr }, &[i]);
+                    unsafe { pool.dealloc(ptr) };
+                }
+            });
+        }
+    });
+    assert!(pool.allocated() <= 8);
+    assert_eq!(pool.len(), pool.allocated());
+}
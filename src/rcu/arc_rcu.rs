@@ -1,20 +1,26 @@
-use crate::check;
 use super::*;
+use crate::check;
 use std_reset::{prelude::Deref, traits::as_prim::AsPrim};
 
 #[derive(Deref)]
-pub struct ArcRcu<T> {
+pub struct ArcRcu<T, A: RcuAlloc<T> = Global> {
     #[deref]
-    rcu: Rcu<T>,
+    rcu: Rcu<T, A>,
 }
 
-unsafe impl<T> Sync for ArcRcu<T> where T: Send {}
-unsafe impl<T> Send for ArcRcu<T> where T: Send {}
+unsafe impl<T, A: RcuAlloc<T>> Sync for ArcRcu<T, A> where T: Send, A: Sync {}
+unsafe impl<T, A: RcuAlloc<T>> Send for ArcRcu<T, A> where T: Send, A: Send {}
 
 impl<T: Clone> ArcRcu<T> {
     pub fn new(data: T) -> Self {
+        Self::new_in(data, Global)
+    }
+}
+impl<T: Clone, A: RcuAlloc<T>> ArcRcu<T, A> {
+    /// Версии данных будут размещаться в `alloc`
+    pub fn new_in(data: T, alloc: A) -> Self {
         Self {
-            rcu: Rcu::new(data),
+            rcu: Rcu::new_in(data, alloc),
         }
     }
     pub fn load(&self) -> T {
@@ -26,7 +32,7 @@ impl<T: Clone> ArcRcu<T> {
         loop {
             let mut changed_data = unsafe { &mut *(load_data.clone()) }.clone();
             f(&mut changed_data);
-            let new_ptr = Box::into_raw(Box::new(changed_data));
+            let new_ptr = self.alloc.alloc(changed_data);
 
             match self
                 .ptr
@@ -40,7 +46,7 @@ impl<T: Clone> ArcRcu<T> {
                 Err(e) => {
                     load_data = e;
                     unsafe {
-                        Box::from_raw(new_ptr);
+                        self.alloc.dealloc(new_ptr);
                     }
                 }
             }
@@ -50,9 +56,13 @@ impl<T: Clone> ArcRcu<T> {
 
 #[test]
 fn check_ram_consumption() {
+    let _measured = crate::allocator::MEASURED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
     let res = (0..4)
         .map(|_| {
             let arcrcu = check! {
+                crate::allocator::GLOBAL;
                 let rcu = ArcRcu::new(0);
                 thread::scope(|s| {
                     for _ in 0..1_000 {
@@ -67,6 +77,7 @@ fn check_ram_consumption() {
                 });
             };
             let rcu = check!(
+                crate::allocator::GLOBAL;
                 let rcu = Rcu::new(0);
                 thread::scope(|s| {
                     for _ in 0..1_000 {
@@ -80,31 +91,38 @@ fn check_ram_consumption() {
                     }
                 });
             );
-            assert!(arcrcu < rcu);
-            [
-                rcu.0 as f64 / arcrcu.0 as f64,
-                rcu.1 as f64 - arcrcu.1 as f64,
-                arcrcu.2.div_duration_f64(rcu.2),
-            ]
+            assert!(arcrcu.1.allocations >= 1_000_000);
+            assert!(rcu.1.allocations >= 1_000_000);
+            // ArcRcu пока так же как и Rcu не освобождает старые версии
+            assert!(arcrcu.1.live_bytes >= (1_000_000 * size_of::<i32>() / 2) as isize);
+            assert!(rcu.1.live_bytes >= (1_000_000 * size_of::<i32>() / 2) as isize);
+            arcrcu.2.div_duration_f64(rcu.2)
         })
-        .fold([const { Vec::new() }; 3], |mut arr, curr| {
-            arr.iter_mut().enumerate().for_each(|(i, ar)| {
-                ar.push(curr[i]);
-            });
-            arr
-        });
+        .collect::<Vec<_>>();
 
     println!(
-        "По окончанию теста:\n{}",
-        [
-            |arg| format!("\trcu тратит в общем памяти в {:.2} раз меньше", arg),
-            |arg| format!("\trcu содержит ленивых указателей на {:.}", arg),
-            |arg| format!("\trcugc выполняется в {:.2} медленнее", arg),
-        ]
-        .iter()
-        .zip(res.iter())
-        .map(|(f, res)| { f(res.iter().sum::<f64>() / res.len().as_::<f64>()) })
-        .collect::<Vec<_>>()
-        .join("\n")
+        "По окончанию теста ({}):\n\tarcrcu выполняется в {:.2} медленнее",
+        crate::allocator::NAME,
+        res.iter().sum::<f64>() / res.len().as_::<f64>()
     );
 }
+
+#[test]
+fn versions_from_pool() {
+    use super::alloc::Pool;
+
+    let pool = Pool::new();
+    let rcu = ArcRcu::new_in(0, &pool);
+    thread::scope(|s| {
+        for _ in 0..10 {
+            s.spawn(|| {
+                for _ in 0..1_000 {
+                    rcu.change(|data| *data += 1);
+                }
+            });
+        }
+    });
+    assert_eq!(rcu.load(), 10_000);
+    // старые версии не освобождаются, поэтому все они взяты из пула
+    assert!(pool.allocated() > 10_000);
+}
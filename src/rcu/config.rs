@@ -0,0 +1,181 @@
+use super::*;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Weak,
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+/// Причина, по которой новая версия конфигурации не была опубликована
+#[derive(Debug)]
+pub enum ReloadError<E> {
+    Io(io::Error),
+    Parse(E),
+}
+
+type Parser<T, E> = Box<dyn Fn(&str) -> Result<T, E> + Send + Sync>;
+
+/// Конфигурация, которая читается из файла и публикуется через [`RcuGC`].
+///
+/// Читатели никогда не блокируются: [`RcuConfig::load`] просто клонирует
+/// текущую версию. Если файл не удалось прочитать или распарсить,
+/// то остаётся предыдущая версия
+pub struct RcuConfig<T, E> {
+    rcu: RcuGC<T>,
+    path: PathBuf,
+    parser: Parser<T, E>,
+    // время изменения файла, из которого была получена текущая версия
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl<T: Clone, E> RcuConfig<T, E> {
+    pub fn open(
+        path: impl AsRef<Path>,
+        parser: impl Fn(&str) -> Result<T, E> + Send + Sync + 'static,
+    ) -> Result<Self, ReloadError<E>> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified(&path).map_err(ReloadError::Io)?;
+        let text = fs::read_to_string(&path).map_err(ReloadError::Io)?;
+        let data = parser(&text).map_err(ReloadError::Parse)?;
+        Ok(Self {
+            rcu: RcuGC::new(data),
+            path,
+            parser: Box::new(parser),
+            modified: Mutex::new(Some(modified)),
+        })
+    }
+    pub fn load(&self) -> T {
+        self.rcu.load()
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Перечитывает файл и публикует новую версию
+    pub fn reload(&self) -> Result<(), ReloadError<E>> {
+        let modified = modified(&self.path).ok();
+        let text = fs::read_to_string(&self.path).map_err(ReloadError::Io)?;
+        let data = (self.parser)(&text).map_err(ReloadError::Parse)?;
+        self.rcu.change(|config| {
+            *config = data.clone();
+        });
+        // время изменения запоминаем только после успешного парсинга:
+        // при грубой точности времени дописанный файл может сохранить время
+        // недописанного, и тогда его бы больше не перечитали
+        *self.modified.lock().unwrap() = modified;
+        Ok(())
+    }
+    /// Перечитывает файл, только если он изменился с момента последнего чтения.
+    /// Возвращает `true`, если была опубликована новая версия
+    pub fn reload_if_changed(&self) -> Result<bool, ReloadError<E>> {
+        let modified = modified(&self.path).map_err(ReloadError::Io)?;
+        if *self.modified.lock().unwrap() == Some(modified) {
+            return Ok(false);
+        }
+        self.reload().map(|_| true)
+    }
+}
+
+impl<T, E> RcuConfig<T, E>
+where
+    T: Clone + Send + 'static,
+    E: 'static,
+{
+    /// Запускает поток, который опрашивает файл с интервалом `interval`.
+    /// Поток завершается, когда удаляется последний [`Arc`] на конфигурацию
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let config: Weak<Self> = Arc::downgrade(self);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let Some(config) = config.upgrade() else {
+                break;
+            };
+            // при ошибке остаётся предыдущая версия
+            let _ = config.reload_if_changed();
+        })
+    }
+}
+
+fn modified(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path)?.modified()
+}
+
+#[cfg(test)]
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lf-structs-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn reload_keeps_previous_version() {
+    let path = temp_file("reload_keeps_previous_version", "1");
+    let config = RcuConfig::open(&path, |text| text.trim().parse::<u32>()).unwrap();
+    assert_eq!(config.load(), 1);
+
+    fs::write(&path, "not a number").unwrap();
+    assert!(matches!(config.reload(), Err(ReloadError::Parse(_))));
+    assert_eq!(config.load(), 1);
+
+    fs::write(&path, "2").unwrap();
+    config.reload().unwrap();
+    assert_eq!(config.load(), 2);
+
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(config.reload(), Err(ReloadError::Io(_))));
+    assert_eq!(config.load(), 2);
+}
+
+#[test]
+fn broken_file_is_retried_with_same_mtime() {
+    let path = temp_file("broken_file_is_retried_with_same_mtime", "1");
+    let config = RcuConfig::open(&path, |text| text.trim().parse::<u32>()).unwrap();
+
+    fs::write(&path, "2 недописан").unwrap();
+    let mtime = modified(&path).unwrap();
+    assert!(matches!(
+        config.reload_if_changed(),
+        Err(ReloadError::Parse(_))
+    ));
+
+    // запись закончилась в пределах той же отметки времени
+    fs::write(&path, "2").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    assert!(config.reload_if_changed().unwrap());
+    assert_eq!(config.load(), 2);
+    assert!(!config.reload_if_changed().unwrap());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn watch() {
+    let path = temp_file("watch", "0");
+    let config = Arc::new(RcuConfig::open(&path, |text| text.trim().parse::<u32>()).unwrap());
+    let watcher = config.watch(Duration::from_millis(10));
+
+    // читатели работают параллельно с перезагрузкой и всегда видят целую версию
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let start = Instant::now();
+                while config.load() != 1 {
+                    assert!(start.elapsed() < Duration::from_secs(5));
+                    thread::yield_now();
+                }
+            });
+        }
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            fs::write(&path, "1").unwrap();
+        });
+    });
+
+    drop(config);
+    watcher.join().unwrap();
+    fs::remove_file(&path).unwrap();
+}
@@ -1,3 +1,4 @@
+use alloc::{Global, RcuAlloc};
 use arc_rcu::ArcRcu;
 use rcu_with_garbage_collector::RcuGC;
 use std::cell::{RefCell, UnsafeCell};
@@ -10,15 +11,18 @@ use std::time::Instant;
 use std::{ops::Deref, sync::atomic::AtomicPtr};
 use std_reset::prelude::Display;
 
+pub mod alloc;
 pub mod rcu_with_garbage_collector;
 pub mod arc_rcu;
+pub mod config;
 
 #[derive(Debug)]
-pub struct Rcu<T> {
+pub struct Rcu<T, A: RcuAlloc<T> = Global> {
     ptr: AtomicPtr<T>,
+    alloc: A,
 }
 
-impl<T: Display + Debug> Display for Rcu<T> {
+impl<T: Display + Debug, A: RcuAlloc<T>> Display for Rcu<T, A> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Rcu")
             .field("ptr", &unsafe { self.ptr.load(Relaxed).as_ref() })
@@ -26,28 +30,36 @@ impl<T: Display + Debug> Display for Rcu<T> {
     }
 }
 
-impl<T: Clone> Clone for Rcu<T> {
+impl<T: Clone, A: RcuAlloc<T> + Clone> Clone for Rcu<T, A> {
     fn clone(&self) -> Self {
         Self {
             ptr: AtomicPtr::new(
                 if let Some(t) = unsafe { self.ptr.load(Relaxed).as_ref() } {
-                    Box::into_raw(Box::new(t.clone()))
+                    self.alloc.alloc(t.clone())
                 } else {
                     std::ptr::null_mut()
                 },
             ),
+            alloc: self.alloc.clone(),
         }
     }
 }
 
 impl<T> Rcu<T> {
     pub fn new(data: T) -> Self {
+        Self::new_in(data, Global)
+    }
+}
+impl<T, A: RcuAlloc<T>> Rcu<T, A> {
+    /// Версии данных будут размещаться в `alloc`, например в [`alloc::Pool`]
+    pub fn new_in(data: T, alloc: A) -> Self {
         Self {
-            ptr: AtomicPtr::new(Box::into_raw(Box::new(data))),
+            ptr: AtomicPtr::new(alloc.alloc(data)),
+            alloc,
         }
     }
 }
-impl<T: Clone> Rcu<T> {
+impl<T: Clone, A: RcuAlloc<T>> Rcu<T, A> {
     pub fn load(&self) -> T {
         unsafe { &*self.ptr.load(Relaxed) }.clone()
     }
@@ -62,7 +74,7 @@ impl<T: Clone> Rcu<T> {
         loop {
             let mut changed_data = unsafe { &mut *load_data }.clone();
             f(&mut changed_data);
-            let new_ptr = Box::into_raw(Box::new(changed_data));
+            let new_ptr = self.alloc.alloc(changed_data);
             match self
                 .ptr
                 .compare_exchange(load_data, new_ptr, AcqRel, Relaxed)
@@ -76,7 +88,7 @@ impl<T: Clone> Rcu<T> {
                     load_data = e;
                     unsafe {
                         // предотвращение утечки
-                        Box::from_raw(new_ptr);
+                        self.alloc.dealloc(new_ptr);
                     }
                 }
             }
@@ -173,12 +185,14 @@ fn counter() {
     println!("{} {:?}", counter.lock().unwrap(), start.elapsed());
 
     let counter = AtomicU32::new(1);
+    // все прочитают счётчик раньше, чем кто-то его изменит
+    let loaded = crate::coordination::Barrier::new(9);
     let start = Instant::now();
     thread::scope(|s| {
         for _ in 1..=9 {
             s.spawn(|| {
                 let c = counter.load(Relaxed);
-                thread::sleep(std::time::Duration::from_secs(1));
+                loaded.wait();
                 counter.fetch_add(c, Relaxed);
             });
         }
@@ -213,8 +227,6 @@ fn pref() {
 
     let rcu_duration = rcu_start.elapsed();
     let rcu_result = rcu.load();
-    thread::sleep(std::time::Duration::from_secs(4));
-    println!("{}", rcu.load());
 
     // Тестирование с использованием Mutex
     let mutex_data = Arc::new(Mutex::new(0usize));
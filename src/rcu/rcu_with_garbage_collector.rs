@@ -6,20 +6,26 @@ use std::{
 use std_reset::{prelude::Deref, traits::as_prim::AsPrim};
 
 #[derive(Deref)]
-pub struct RcuGC<T> {
+pub struct RcuGC<T, A: RcuAlloc<T> = Global> {
     #[deref]
-    rcu: Rcu<T>,
+    rcu: Rcu<T, A>,
     is_used: AtomicU64,
     garbage_collector: Mutex<Vec<*mut T>>,
 }
 
-unsafe impl<T> Sync for RcuGC<T> where T: Send {}
-unsafe impl<T> Send for RcuGC<T> where T: Send {}
+unsafe impl<T, A: RcuAlloc<T>> Sync for RcuGC<T, A> where T: Send, A: Sync {}
+unsafe impl<T, A: RcuAlloc<T>> Send for RcuGC<T, A> where T: Send, A: Send {}
 
 impl<T: Clone> RcuGC<T> {
     pub fn new(data: T) -> Self {
+        Self::new_in(data, Global)
+    }
+}
+impl<T: Clone, A: RcuAlloc<T>> RcuGC<T, A> {
+    /// Старые версии, собранные сборщиком, возвращаются в `alloc`
+    pub fn new_in(data: T, alloc: A) -> Self {
         Self {
-            rcu: Rcu::new(data),
+            rcu: Rcu::new_in(data, alloc),
             garbage_collector: Mutex::new(Vec::new()),
             is_used: AtomicU64::new(0),
         }
@@ -28,26 +34,29 @@ impl<T: Clone> RcuGC<T> {
         self.rcu.load()
     }
     pub fn change(&self, f: impl Fn(&mut T)) {
-        let mut load_data = self.ptr.load(Acquire);
-        self.is_used.fetch_add(1, Relaxed);
+        // поток отмечается до чтения указателя: пока он отмечен, прочитанную версию
+        // не освободят, и пул не выдаст её слот под новую версию с тем же адресом,
+        // иначе compare_exchange прошёл бы по устаревшей копии
+        self.is_used.fetch_add(1, SeqCst);
+        let mut load_data = self.ptr.load(SeqCst);
         loop {
             let mut changed_data = unsafe { &mut *load_data }.clone();
             f(&mut changed_data);
-            let new_ptr = Box::into_raw(Box::new(changed_data));
+            let new_ptr = self.alloc.alloc(changed_data);
             match self
                 .ptr
-                .compare_exchange(load_data, new_ptr, AcqRel, Relaxed)
+                .compare_exchange(load_data, new_ptr, SeqCst, SeqCst)
             {
                 Ok(load_data) => {
                     // если garbage_collector заблокирован, то это значит происходит очистка старых указателей
                     self.garbage_collector.lock().unwrap().push(load_data);
-                    self.is_used.fetch_sub(1, Relaxed);
+                    self.is_used.fetch_sub(1, SeqCst);
                     break;
                 }
                 Err(e) => {
                     load_data = e;
                     unsafe {
-                        Box::from_raw(new_ptr);
+                        self.alloc.dealloc(new_ptr);
                     }
                 }
             }
@@ -56,10 +65,15 @@ impl<T: Clone> RcuGC<T> {
         // то значит старые указатели уже никто не использует, и их можно удалить
         // В любом случае самый последний поток, сможет очистить все указатели,
         //если никто из предыдущих этого не сделал
-        if self.is_used.load(Relaxed) == 0 {
+        if self.is_used.load(SeqCst) == 0 {
             let mut y = self.garbage_collector.lock().unwrap();
+            // пока ждали блокировку, другой поток мог отметиться и прочитать версию,
+            // которую после этого положили в мусор
+            if self.is_used.load(SeqCst) != 0 {
+                return;
+            }
             while let Some(ptr) = y.pop() {
-                drop(unsafe { Box::from_raw(ptr) });
+                unsafe { self.alloc.dealloc(ptr) };
             }
         }
     }
@@ -85,25 +99,59 @@ fn check_garabage_collector() {
     assert_eq!(rcu.load(), 1_000_000);
 }
 
+#[test]
+fn versions_from_pool() {
+    use super::alloc::Pool;
+
+    let pool = Pool::new();
+    let rcu = RcuGC::new_in(String::new(), &pool);
+    for i in 0..1_000 {
+        rcu.change(|data| {
+            *data = format!("version {i}");
+        });
+    }
+    assert_eq!(rcu.load(), "version 999");
+    // без конкуренции старая версия сразу возвращается в пул
+    assert!(pool.allocated() <= 2);
+
+    let pool = Pool::new();
+    let rcu = RcuGC::new_in(0, &pool);
+    thread::scope(|s| {
+        for _ in 0..100 {
+            s.spawn(|| {
+                for _ in 0..1_000 {
+                    rcu.change(|data| {
+                        *data += 1;
+                    });
+                }
+            });
+        }
+    });
+    assert_eq!(rcu.load(), 100_000);
+    assert!(pool.allocated() < 100_000);
+}
+
 
 
+/// Замеряет код через [`MemoryProbe`](crate::allocator::MemoryProbe) на `$alloc`,
+/// установленном глобальным [`Counting`](crate::allocator::Counting).
+/// Возвращает замер внутри блока (пока локальные переменные ещё живы),
+/// замер после блока (утечка из-за указателей без времени жизни) и время выполнения
 #[macro_export]
 macro_rules! check {
-    ($($code:tt)*) => {
+    ($alloc:expr; $($code:tt)*) => {
         {
-            use memory_stats::memory_stats;
+            use $crate::allocator::MemoryProbe;
 
-            let start_time = Instant::now();
-            let start_lazy_ptr_mem = memory_stats().unwrap().physical_mem;
-            // не важно что код в блоке {}, т.к. мы считываем разницу
-            // в утечке памяти из-зи указателей без времени жизни
+            let start_time = ::std::time::Instant::now();
+            let lazy_ptr_probe = MemoryProbe::start(&$alloc);
             let general_mem = {
-                let start_general_mem = memory_stats().unwrap().physical_mem;
+                let general_probe = MemoryProbe::start(&$alloc);
                 $($code)*
-                memory_stats().unwrap().physical_mem - start_general_mem
+                general_probe.finish()
             };
-            let lazy_ptr_mem = memory_stats().unwrap().physical_mem - start_lazy_ptr_mem;
-            
+            let lazy_ptr_mem = lazy_ptr_probe.finish();
+
             (
                 general_mem,
                 lazy_ptr_mem,
@@ -115,9 +163,13 @@ macro_rules! check {
 
 #[test]
 fn check_ram_consumption() {
+    let _measured = crate::allocator::MEASURED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
     let res = (0..4)
         .map(|_| {
             let rcugc = check! {
+                crate::allocator::GLOBAL;
                 let rcu = RcuGC::new(0);
                 thread::scope(|s| {
                     for _ in 0..1_000 {
@@ -132,6 +184,7 @@ fn check_ram_consumption() {
                 });
             };
             let rcu = check!(
+                crate::allocator::GLOBAL;
                 let rcu = Rcu::new(0);
                 thread::scope(|s| {
                     for _ in 0..1_000 {
@@ -145,31 +198,21 @@ fn check_ram_consumption() {
                     }
                 });
             );
-            assert!(rcugc < rcu);
-            [
-                rcu.0 as f64 / rcugc.0 as f64,
-                rcu.1 as f64 - rcugc.1 as f64,
-                rcugc.2.div_duration_f64(rcu.2),
-            ]
+            // каждое изменение выделяет новую версию
+            assert!(rcugc.1.allocations >= 1_000_000);
+            assert!(rcu.1.allocations >= 1_000_000);
+            // сборщик освобождает все старые версии
+            assert!(rcugc.1.frees >= 1_000_000);
+            // а rcu их теряет
+            assert!(rcugc.1.live_bytes < rcu.1.live_bytes);
+            assert!(rcu.1.live_bytes >= (1_000_000 * size_of::<i32>() / 2) as isize);
+            rcugc.2.div_duration_f64(rcu.2)
         })
-        .fold([const { Vec::new() }; 3], |mut arr, curr| {
-            arr.iter_mut().enumerate().for_each(|(i, ar)| {
-                ar.push(curr[i]);
-            });
-            arr
-        });
+        .collect::<Vec<_>>();
 
     println!(
-        "По окончанию теста:\n{}",
-        [
-            |arg| format!("\trcu тратит в общем памяти в {:.2} раз меньше", arg),
-            |arg| format!("\trcu содержит ленивых указателей на {:.}", arg),
-            |arg| format!("\trcugc выполняется в {:.2} медленнее", arg),
-        ]
-        .iter()
-        .zip(res.iter())
-        .map(|(f, res)| { f(res.iter().sum::<f64>() / res.len().as_::<f64>()) })
-        .collect::<Vec<_>>()
-        .join("\n")
+        "По окончанию теста ({}):\n\trcugc выполняется в {:.2} медленнее",
+        crate::allocator::NAME,
+        res.iter().sum::<f64>() / res.len().as_::<f64>()
     );
 }
@@ -0,0 +1,339 @@
+use atomic_wait::{wait, wake_all};
+use std::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU32, Ordering::*},
+};
+
+/// Блокировка читатель-писатель на одном futex из atomic-wait.
+///
+/// Для данных, которые дорого клонировать ради [`Rcu`](crate::Rcu).
+/// Писатели в приоритете: пока писатель ждёт, новые читатели не входят
+pub struct RwLock<T> {
+    // младшие биты это число читателей, старшие это флаги ниже
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+const READERS: u32 = (1 << 29) - 1;
+const WRITE_LOCKED: u32 = 1 << 29;
+const READERS_WAITING: u32 = 1 << 30;
+const WRITER_WAITING: u32 = 1 << 31;
+
+impl<T> RwLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            let s = self.state.load(Relaxed);
+            if s & (WRITE_LOCKED | WRITER_WAITING) == 0 {
+                continue;
+            }
+            if s & READERS_WAITING == 0
+                && self
+                    .state
+                    .compare_exchange(s, s | READERS_WAITING, Relaxed, Relaxed)
+                    .is_err()
+            {
+                continue;
+            }
+            wait(&self.state, s | READERS_WAITING);
+        }
+    }
+    /// Не входит, если внутри или в ожидании есть писатель
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let mut s = self.state.load(Relaxed);
+        while s & (WRITE_LOCKED | WRITER_WAITING) == 0 {
+            assert!(s & READERS != READERS, "Слишком много читателей");
+            match self.state.compare_exchange_weak(s, s + 1, Acquire, Relaxed) {
+                Ok(_) => return Some(RwLockReadGuard { lock: self }),
+                Err(e) => s = e,
+            }
+        }
+        None
+    }
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_write() {
+                return guard;
+            }
+            let s = self.state.load(Relaxed);
+            if s & (READERS | WRITE_LOCKED) == 0 {
+                continue;
+            }
+            // флаг не даёт войти новым читателям
+            if s & WRITER_WAITING == 0
+                && self
+                    .state
+                    .compare_exchange(s, s | WRITER_WAITING, Relaxed, Relaxed)
+                    .is_err()
+            {
+                continue;
+            }
+            wait(&self.state, s | WRITER_WAITING);
+        }
+    }
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        let mut s = self.state.load(Relaxed);
+        // флаги ожидания остаются: снимет их и разбудит всех тот, кто выйдет
+        while s & (READERS | WRITE_LOCKED) == 0 {
+            match self
+                .state
+                .compare_exchange_weak(s, s | WRITE_LOCKED, Acquire, Relaxed)
+            {
+                Ok(_) => return Some(RwLockWriteGuard { lock: self }),
+                Err(e) => s = e,
+            }
+        }
+        None
+    }
+    fn read_unlock(&self) {
+        let s = self.state.fetch_sub(1, Release);
+        // последний читатель пропускает ждущего писателя
+        if s & READERS == 1 && s & WRITER_WAITING != 0 {
+            wake_all(&self.state);
+        }
+    }
+    fn write_unlock(&self) {
+        // все проснувшиеся снова поставят свои флаги, если не успеют войти
+        if self.state.swap(0, Release) & (READERS_WAITING | WRITER_WAITING) != 0 {
+            wake_all(&self.state);
+        }
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+#[must_use]
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.read_unlock();
+    }
+}
+
+#[must_use]
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> RwLockWriteGuard<'a, T> {
+    /// Превращает запись в чтение, не отпуская блокировку.
+    ///
+    /// Флаги ожидания снимаются, как при выходе писателя: флаг писателя мог
+    /// остаться от того, кто уже вошёл, и тогда запер бы читателей навсегда.
+    /// Проснувшиеся писатели снова поставят его сами
+    pub fn downgrade(guard: Self) -> RwLockReadGuard<'a, T> {
+        let lock = guard.lock;
+        std::mem::forget(guard);
+        if lock.state.swap(1, Release) & (READERS_WAITING | WRITER_WAITING) != 0 {
+            wake_all(&lock.state);
+        }
+        RwLockReadGuard { lock }
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.write_unlock();
+    }
+}
+
+#[test]
+fn readers_and_writers() {
+    use std::thread;
+
+    let lock = RwLock::new((0u64, 0u64));
+    thread::scope(|s| {
+        for i in 0..16 {
+            let lock = &lock;
+            s.spawn(move || {
+                for _ in 0..2_000 {
+                    if i % 4 == 0 {
+                        let mut data = lock.write();
+                        data.0 += 1;
+                        data.1 += 1;
+                    } else {
+                        let data = lock.read();
+                        // писатель не виден наполовину
+                        assert_eq!(data.0, data.1);
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(lock.into_inner(), (8_000, 8_000));
+}
+
+#[test]
+fn try_read_and_try_write() {
+    let lock = RwLock::new(0);
+    let read = lock.read();
+    assert!(lock.try_read().is_some());
+    assert!(lock.try_write().is_none());
+    drop(read);
+
+    let mut write = lock.try_write().unwrap();
+    *write = 1;
+    assert!(lock.try_read().is_none());
+    assert!(lock.try_write().is_none());
+    drop(write);
+    assert_eq!(*lock.read(), 1);
+}
+
+#[test]
+fn writer_preference() {
+    use crate::coordination::CountDownLatch;
+    use std::thread;
+
+    let lock = RwLock::new(0);
+    let read = lock.read();
+    let writer_started = CountDownLatch::new(1);
+    thread::scope(|s| {
+        s.spawn(|| {
+            writer_started.count_down();
+            *lock.write() += 1;
+        });
+        writer_started.wait();
+        // писатель ставит флаг, после чего новые читатели не входят
+        while lock.state.load(Relaxed) & WRITER_WAITING == 0 {
+            thread::yield_now();
+        }
+        assert!(lock.try_read().is_none());
+        drop(read);
+    });
+    assert_eq!(*lock.read(), 1);
+}
+
+#[test]
+fn downgrade() {
+    use std::thread;
+
+    let lock = RwLock::new(0);
+    let mut write = lock.write();
+    *write = 1;
+    thread::scope(|s| {
+        let reader = s.spawn(|| *lock.read());
+        let read = RwLockWriteGuard::downgrade(write);
+        assert_eq!(*read, 1);
+        assert!(lock.try_write().is_none());
+        assert_eq!(reader.join().unwrap(), 1);
+    });
+    assert!(lock.try_write().is_some());
+}
+
+#[test]
+fn downgrade_after_contended_write() {
+    use std::thread;
+
+    let lock = RwLock::new(0);
+    let read = lock.read();
+    thread::scope(|s| {
+        let writer = s.spawn(|| {
+            let mut write = lock.write();
+            *write = 1;
+            drop(RwLockWriteGuard::downgrade(write));
+        });
+        // писатель ждёт читателя, и его флаг переживает вход писателя
+        while lock.state.load(Relaxed) & WRITER_WAITING == 0 {
+            thread::yield_now();
+        }
+        drop(read);
+        writer.join().unwrap();
+    });
+    assert!(lock.try_read().is_some());
+    assert_eq!(*lock.read(), 1);
+}
+
+/// Нагрузка из `rcu::counter`, где чтений больше, чем записей.
+/// Возвращает количество записей
+#[cfg(test)]
+fn counter(read: impl Fn() -> u64 + Sync, write: impl Fn() + Sync) -> u64 {
+    use std::thread;
+
+    const THREADS: usize = 8;
+    const OPS: usize = 2_000;
+    const WRITE_EVERY: usize = 10;
+
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for i in 0..OPS {
+                    if i % WRITE_EVERY == 0 {
+                        write();
+                    } else {
+                        std::hint::black_box(read());
+                    }
+                }
+            });
+        }
+    });
+    (THREADS * OPS / WRITE_EVERY) as u64
+}
+
+#[bench]
+fn counter_rwlock(b: &mut test::Bencher) {
+    let lock = RwLock::new(0u64);
+    let mut writes = 0;
+    b.iter(|| writes += counter(|| *lock.read(), || *lock.write() += 1));
+    assert_eq!(*lock.read(), writes);
+}
+
+#[bench]
+fn counter_std_rwlock(b: &mut test::Bencher) {
+    let lock = std::sync::RwLock::new(0u64);
+    let mut writes = 0;
+    b.iter(|| writes += counter(|| *lock.read().unwrap(), || *lock.write().unwrap() += 1));
+    assert_eq!(*lock.read().unwrap(), writes);
+}
+
+#[bench]
+fn counter_rcu(b: &mut test::Bencher) {
+    let rcu = crate::Rcu::new(0u64);
+    let mut writes = 0;
+    b.iter(|| writes += counter(|| rcu.load(), || rcu.change(|c| *c += 1)));
+    assert_eq!(rcu.load(), writes);
+}
@@ -0,0 +1,51 @@
+use super::{optimised::Semaphore, SemaphoreFull};
+use std_reset::prelude::Deref;
+
+/// Семафор с максимумом ресурсов, у которого лишний возврат ресурса
+/// это ошибка [`SemaphoreFull`], а не паника
+#[derive(Deref)]
+pub struct BoundedSemaphore {
+    #[deref]
+    semaphore: Semaphore,
+}
+
+impl BoundedSemaphore {
+    pub const fn new(count_of_resurses: u32, max: u32) -> Self {
+        Self {
+            semaphore: Semaphore::with_max(count_of_resurses, max),
+        }
+    }
+    pub fn signal(&self) -> Result<(), SemaphoreFull> {
+        self.semaphore.try_signal()
+    }
+    pub fn signal_n(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.semaphore.try_signal_n(n)
+    }
+}
+
+#[test]
+fn signal_over_max() {
+    let semaphore = BoundedSemaphore::new(2, 2);
+    assert_eq!(semaphore.signal(), Err(SemaphoreFull));
+
+    semaphore.wait_n(2).unwrap();
+    assert_eq!(semaphore.signal_n(2), Ok(()));
+    assert_eq!(semaphore.signal(), Err(SemaphoreFull));
+    assert_eq!(semaphore.try_signal(), Err(SemaphoreFull));
+
+    semaphore.wait().unwrap();
+    assert_eq!(semaphore.signal(), Ok(()));
+}
+
+#[test]
+fn permit_into_full_semaphore_while_panicking() {
+    let semaphore = BoundedSemaphore::new(1, 1);
+    let res = std::panic::catch_unwind(|| {
+        let _permit = semaphore.acquire().unwrap();
+        semaphore.signal().unwrap();
+        // возврат разрешения при раскрутке стека не должен ронять процесс
+        panic!("паника с лишним разрешением");
+    });
+    assert!(res.is_err());
+    assert_eq!(semaphore.available(), 1);
+}
@@ -0,0 +1,163 @@
+//! Общие проверки для всех реализаций [`CountingSemaphore`]
+use super::{
+    fair::FairSemaphore, optimised, priority::PrioritySemaphore, CountingSemaphore, Semaphore,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering::*},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+const THREADS: u32 = 16;
+const ITERATIONS: u32 = 500;
+
+/// После любого числа пар `wait`/`signal` счётчик возвращается к исходному
+fn counts_match<S: CountingSemaphore + Sync>(new: fn(u32) -> S) {
+    let semaphore = new(3);
+    assert_eq!(semaphore.available(), 3);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ITERATIONS {
+                    semaphore.wait().unwrap();
+                    semaphore.signal();
+                }
+            });
+        }
+    });
+    assert_eq!(semaphore.available(), 3);
+}
+
+/// Внутри никогда не оказывается больше потоков, чем ресурсов
+fn never_oversubscribed<S: CountingSemaphore + Sync>(new: fn(u32) -> S) {
+    const PERMITS: u32 = 4;
+
+    let semaphore = new(PERMITS);
+    let (inside, max_inside) = (AtomicU32::new(0), AtomicU32::new(0));
+    thread::scope(|s| {
+        for i in 0..THREADS {
+            let (semaphore, inside, max_inside) = (&semaphore, &inside, &max_inside);
+            s.spawn(move || {
+                for _ in 0..ITERATIONS {
+                    // половина потоков пробует без ожидания
+                    if i % 2 == 0 {
+                        semaphore.wait().unwrap();
+                    } else if !semaphore.try_wait() {
+                        continue;
+                    }
+                    let now = inside.fetch_add(1, AcqRel) + 1;
+                    max_inside.fetch_max(now, Relaxed);
+                    thread::yield_now();
+                    inside.fetch_sub(1, AcqRel);
+                    semaphore.signal();
+                }
+            });
+        }
+    });
+    assert!(max_inside.into_inner() <= PERMITS);
+    assert_eq!(semaphore.available(), PERMITS);
+}
+
+/// Каждый `signal` будит ровно одного ожидающего, даже если
+/// ожидающие и сигналы приходят вперемешку
+fn no_lost_wakeup<S: CountingSemaphore + Send + Sync + 'static>(new: fn(u32) -> S) {
+    let semaphore = Arc::new(new(0));
+    let (done, finished) = mpsc::channel();
+    for _ in 0..THREADS {
+        let (semaphore, done) = (semaphore.clone(), done.clone());
+        thread::spawn(move || {
+            for _ in 0..ITERATIONS {
+                semaphore.wait().unwrap();
+            }
+            done.send(()).unwrap();
+        });
+    }
+    for _ in 0..THREADS * ITERATIONS {
+        semaphore.signal();
+    }
+    // потерянное пробуждение оставит кого-то ждать навсегда
+    for _ in 0..THREADS {
+        finished
+            .recv_timeout(Duration::from_secs(10))
+            .expect("пробуждение потерялось");
+    }
+    assert_eq!(semaphore.available(), 0);
+}
+
+/// `try_wait` забирает ровно столько ресурсов, сколько есть
+fn try_wait_exact<S: CountingSemaphore + Sync>(new: fn(u32) -> S) {
+    const PERMITS: u32 = 100;
+
+    let semaphore = new(PERMITS);
+    let taken = AtomicU32::new(0);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                while semaphore.try_wait() {
+                    taken.fetch_add(1, Relaxed);
+                }
+            });
+        }
+    });
+    assert_eq!(taken.into_inner(), PERMITS);
+    assert_eq!(semaphore.available(), 0);
+    assert!(!semaphore.try_wait());
+    semaphore.signal();
+    assert_eq!(semaphore.available(), 1);
+}
+
+macro_rules! conformance {
+    ($($name:ident: $new:expr;)*) => {
+        $(
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn counts_match() {
+                    super::counts_match($new);
+                }
+                #[test]
+                fn never_oversubscribed() {
+                    super::never_oversubscribed($new);
+                }
+                #[test]
+                fn no_lost_wakeup() {
+                    super::no_lost_wakeup($new);
+                }
+                #[test]
+                fn try_wait_exact() {
+                    super::try_wait_exact($new);
+                }
+            }
+        )*
+    };
+}
+
+conformance! {
+    condvar: Semaphore::new;
+    lock_free: optimised::Semaphore::new;
+    fair: FairSemaphore::new;
+    priority: PrioritySemaphore::new;
+}
+
+#[cfg(target_os = "linux")]
+fn shared(count_of_resurses: u32) -> super::SharedSemaphore {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "lf-structs-conformance-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Relaxed)
+    ));
+    let semaphore = super::SharedSemaphore::create(&path, count_of_resurses).unwrap();
+    // отображение переживает удаление файла
+    std::fs::remove_file(path).unwrap();
+    semaphore
+}
+
+#[cfg(target_os = "linux")]
+conformance! {
+    shared_memory: shared;
+}
@@ -0,0 +1,13 @@
+use super::{permit::Release, Closed};
+
+/// Общий интерфейс считающих семафоров
+pub trait CountingSemaphore: Release {
+    /// Забирает ресурс, дожидаясь его, если свободных нет
+    fn wait(&self) -> Result<(), Closed>;
+    /// Возвращает ресурс
+    fn signal(&self);
+    /// Забирает ресурс, только если он есть прямо сейчас
+    fn try_wait(&self) -> bool;
+    /// Количество свободных ресурсов
+    fn available(&self) -> u32;
+}
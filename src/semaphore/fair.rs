@@ -0,0 +1,161 @@
+use super::{
+    counting::CountingSemaphore,
+    handoff::{Handoff, DEFAULT_PRIORITY},
+    permit::{Release, SemaphorePermit},
+    Closed, SemaphoreFull,
+};
+use std::time::{Duration, Instant};
+
+/// Справедливый семафор: ресурсы передаются ожидающим напрямую
+/// в порядке их прихода, как очередь потоков в `queue_based_locks`.
+///
+/// Это [`PrioritySemaphore`](super::PrioritySemaphore) без старения,
+/// в котором у всех ожидающих одинаковый приоритет
+pub struct FairSemaphore {
+    queue: Handoff,
+}
+
+impl FairSemaphore {
+    pub const fn new(count_of_resurses: u32) -> Self {
+        Self {
+            queue: Handoff::new(count_of_resurses, None),
+        }
+    }
+    pub fn signal(&self) {
+        self.signal_n(1);
+    }
+    /// Возвращает ресурсы. Работает и после закрытия семафора
+    pub fn signal_n(&self, n: u32) {
+        if let Err(e) = self.try_signal_n(n) {
+            panic!("{e}");
+        }
+    }
+    /// Как [`Self::signal`], но вместо паники сообщает о лишнем возврате
+    pub fn try_signal(&self) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(1)
+    }
+    pub fn try_signal_n(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.queue.try_signal_n(n)
+    }
+    pub fn wait(&self) -> Result<(), Closed> {
+        self.queue.wait(DEFAULT_PRIORITY)
+    }
+    /// Забирает ресурс, только если он есть и никто его не ждёт
+    pub fn try_wait(&self) -> bool {
+        self.queue.try_wait()
+    }
+    /// Закрывает семафор: все текущие и будущие ожидания возвращают [`Closed`]
+    pub fn close(&self) {
+        self.queue.close();
+    }
+    pub fn is_closed(&self) -> bool {
+        self.queue.is_closed()
+    }
+    /// Возвращает `false`, если очередь не дошла за `timeout`
+    /// или семафор закрыт
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.wait_deadline(deadline),
+            None => self.wait().is_ok(),
+        }
+    }
+    /// Возвращает `false`, если очередь не дошла к `deadline`
+    /// или семафор закрыт
+    pub fn wait_deadline(&self, deadline: Instant) -> bool {
+        self.queue.wait_deadline(DEFAULT_PRIORITY, deadline)
+    }
+    pub fn acquire(&self) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.wait()?;
+        Ok(SemaphorePermit::new(self, 1))
+    }
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_, Self>> {
+        self.try_wait().then(|| SemaphorePermit::new(self, 1))
+    }
+}
+
+impl Release for FairSemaphore {
+    fn release(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(n)
+    }
+}
+
+impl CountingSemaphore for FairSemaphore {
+    fn wait(&self) -> Result<(), Closed> {
+        FairSemaphore::wait(self)
+    }
+    fn signal(&self) {
+        FairSemaphore::signal(self)
+    }
+    fn try_wait(&self) -> bool {
+        FairSemaphore::try_wait(self)
+    }
+    fn available(&self) -> u32 {
+        self.queue.available()
+    }
+}
+
+#[test]
+fn fifo_order() {
+    use std::{sync::Mutex, thread};
+
+    let semaphore = FairSemaphore::new(0);
+    let order = Mutex::new(Vec::new());
+    thread::scope(|s| {
+        for i in 0..8 {
+            let (semaphore, order) = (&semaphore, &order);
+            s.spawn(move || {
+                semaphore.wait().unwrap();
+                order.lock().unwrap().push(i);
+            });
+            // следующий встаёт в очередь только после предыдущего
+            while semaphore.queue.queued() != i + 1 {
+                thread::yield_now();
+            }
+        }
+        for i in 0..8 {
+            semaphore.signal();
+            while order.lock().unwrap().len() != i + 1 {
+                thread::yield_now();
+            }
+        }
+    });
+    assert_eq!(order.into_inner().unwrap(), (0..8).collect::<Vec<_>>());
+}
+
+#[test]
+fn no_starvation() {
+    use std::{sync::Mutex, thread};
+
+    const THREADS: u32 = 16;
+    const ROUNDS: usize = 50;
+
+    let semaphore = FairSemaphore::new(2);
+    // сколько ждали ресурс и сколько его держали
+    let log = Mutex::new(Vec::with_capacity(THREADS as usize * ROUNDS));
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    let start = Instant::now();
+                    let permit = semaphore.acquire().unwrap();
+                    let waited = start.elapsed();
+                    let start = Instant::now();
+                    thread::sleep(Duration::from_millis(1));
+                    let held = start.elapsed();
+                    drop(permit);
+                    log.lock().unwrap().push((waited, held));
+                }
+            });
+        }
+    });
+    // в очереди перед ожидающим не больше остальных потоков, и они проходят
+    // по двое, поэтому ждать приходится не дольше, чем держат ресурс все потоки
+    let log = log.into_inner().unwrap();
+    let max_wait = log.iter().map(|&(waited, _)| waited).max().unwrap();
+    let max_hold = log.iter().map(|&(_, held)| held).max().unwrap();
+    assert!(
+        max_wait <= max_hold * THREADS,
+        "ожидание {max_wait:?} при удержании до {max_hold:?}"
+    );
+    assert_eq!(semaphore.queue.available(), 2);
+}
@@ -0,0 +1,45 @@
+//! futex через системный вызов: atomic-wait не умеет ждать с таймаутом
+//! и не даёт общего между процессами futex
+use std::{ptr, sync::atomic::AtomicU32, time::Duration};
+
+/// Засыпает, пока в `atomic` лежит `expected`, но не дольше `timeout`.
+/// futex общий, его можно разбудить из другого процесса через [`wake`]
+pub(super) fn wait(atomic: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    futex_wait(atomic, libc::FUTEX_WAIT, expected, timeout);
+}
+
+/// Как [`wait`], но futex приватный, как у atomic-wait,
+/// поэтому спящего будят его `wake_one` и `wake_all`
+pub(super) fn wait_private(atomic: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    futex_wait(
+        atomic,
+        libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+        expected,
+        timeout,
+    );
+}
+
+/// Будит до `n` спящих в [`wait`]
+pub(super) fn wake(atomic: &AtomicU32, n: i32) {
+    unsafe {
+        libc::syscall(libc::SYS_futex, atomic, libc::FUTEX_WAKE, n);
+    }
+}
+
+fn futex_wait(atomic: &AtomicU32, op: libc::c_int, expected: u32, timeout: Option<Duration>) {
+    let timeout = timeout.map(|t| libc::timespec {
+        tv_sec: t.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+        tv_nsec: t.subsec_nanos() as libc::c_long,
+    });
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            atomic,
+            op,
+            expected,
+            timeout
+                .as_ref()
+                .map_or(ptr::null(), |t| t as *const libc::timespec),
+        );
+    }
+}
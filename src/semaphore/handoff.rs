@@ -0,0 +1,254 @@
+//! Очередь, из которой освободившиеся ресурсы передаются ожидающим напрямую.
+//!
+//! Общая для [`FairSemaphore`](super::FairSemaphore) и
+//! [`PrioritySemaphore`](super::PrioritySemaphore): порядок прихода это
+//! частный случай приоритетов, когда у всех ожидающих они равны.
+//! Свободные ресурсы считает обычный [`Semaphore`]
+use super::{Closed, Semaphore, SemaphoreFull};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        atomic::{AtomicU8, Ordering::*},
+        Arc, Mutex,
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+/// Приоритет ожидания без явного приоритета
+pub const DEFAULT_PRIORITY: u32 = 0;
+
+/// Пока в очереди кто-то есть, освободившийся ресурс не попадает в счётчик,
+/// поэтому новый поток не может обогнать тех, кто уже ждёт
+pub(super) struct Handoff {
+    // свободные ресурсы и признак закрытия. Меняются только под `state`,
+    // чтобы ресурс не попал в счётчик, пока его ждут в очереди
+    permits: Semaphore,
+    state: Mutex<State>,
+    // за каждые `aging` ожидания приоритет растёт на единицу, `None` без старения
+    aging: Option<Duration>,
+}
+
+struct State {
+    queue: BinaryHeap<Entry>,
+    // номер следующего пришедшего
+    seq: u64,
+    // от него отсчитывается время прихода для старения
+    epoch: Option<Instant>,
+}
+
+struct Node {
+    thread: Thread,
+    status: AtomicU8,
+}
+
+/// Место в очереди. Старение прибавляет всем ожидающим одинаково,
+/// поэтому порядок между ними не меняется со временем, и его можно
+/// посчитать один раз при постановке в очередь
+struct Entry {
+    // приоритет в единицах старения минус время прихода
+    key: i128,
+    seq: u64,
+    node: Arc<Node>,
+}
+
+impl Ord for Entry {
+    /// Больше тот, кто приоритетнее, при равенстве тот, кто пришёл раньше
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key
+            .cmp(&other.key)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+const WAITING: u8 = 0;
+const GRANTED: u8 = 1;
+const CLOSED: u8 = 2;
+
+impl Node {
+    fn finish(&self, status: u8) {
+        self.status.store(status, Release);
+        self.thread.unpark();
+    }
+    fn status(&self) -> Result<bool, Closed> {
+        match self.status.load(Acquire) {
+            WAITING => Ok(false),
+            GRANTED => Ok(true),
+            _ => Err(Closed),
+        }
+    }
+}
+
+impl Handoff {
+    pub(super) const fn new(count_of_resurses: u32, aging: Option<Duration>) -> Self {
+        Self {
+            permits: Semaphore::new(count_of_resurses),
+            state: Mutex::new(State {
+                queue: BinaryHeap::new(),
+                seq: 0,
+                epoch: None,
+            }),
+            aging,
+        }
+    }
+    pub(super) fn try_signal_n(&self, n: u32) -> Result<(), SemaphoreFull> {
+        let mut state = self.state.lock().unwrap();
+        // переполнение проверяется до передачи, чтобы не отдать ресурсы наполовину
+        let rest = n.saturating_sub(state.queue.len() as u32);
+        if rest != 0 {
+            self.permits.try_signal_n(rest)?;
+        }
+        for _ in rest..n {
+            state.queue.pop().unwrap().node.finish(GRANTED);
+        }
+        Ok(())
+    }
+    pub(super) fn wait(&self, priority: u32) -> Result<(), Closed> {
+        let Some(node) = self.enqueue(priority)? else {
+            return Ok(());
+        };
+        while !node.status()? {
+            thread::park();
+        }
+        Ok(())
+    }
+    /// Возвращает `false`, если очередь не дошла к `deadline`
+    /// или семафор закрыт
+    pub(super) fn wait_deadline(&self, priority: u32, deadline: Instant) -> bool {
+        let Ok(node) = self.enqueue(priority) else {
+            return false;
+        };
+        let Some(node) = node else {
+            return true;
+        };
+        loop {
+            match node.status() {
+                Ok(false) => {}
+                granted => return granted.is_ok(),
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::park_timeout(deadline - now);
+        }
+        let mut state = self.state.lock().unwrap();
+        // ресурс мог быть передан уже после таймаута
+        match node.status() {
+            Ok(false) => {}
+            granted => return granted.is_ok(),
+        }
+        state.queue.retain(|entry| !Arc::ptr_eq(&entry.node, &node));
+        false
+    }
+    /// Забирает ресурс, только если он есть и никто его не ждёт.
+    /// Пока очередь не пуста, счётчик пуст, поэтому очередь не нужна
+    pub(super) fn try_wait(&self) -> bool {
+        self.permits.try_wait()
+    }
+    pub(super) fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.permits.close();
+        for entry in state.queue.drain() {
+            entry.node.finish(CLOSED);
+        }
+    }
+    pub(super) fn is_closed(&self) -> bool {
+        self.permits.is_closed()
+    }
+    pub(super) fn available(&self) -> u32 {
+        self.permits.available()
+    }
+    /// Сколько потоков стоит в очереди
+    #[cfg(test)]
+    pub(super) fn queued(&self) -> usize {
+        self.state.lock().unwrap().queue.len()
+    }
+    /// Встаёт в очередь, если свободного ресурса нет
+    fn enqueue(&self, priority: u32) -> Result<Option<Arc<Node>>, Closed> {
+        let mut state = self.state.lock().unwrap();
+        if self.permits.is_closed() {
+            return Err(Closed);
+        }
+        if self.permits.try_wait() {
+            return Ok(None);
+        }
+        let key = match self.aging {
+            Some(aging) => {
+                let now = Instant::now();
+                let since = now - *state.epoch.get_or_insert(now);
+                priority as i128 * aging.as_nanos() as i128 - since.as_nanos() as i128
+            }
+            None => priority as i128,
+        };
+        let node = Arc::new(Node {
+            thread: thread::current(),
+            status: AtomicU8::new(WAITING),
+        });
+        let seq = state.seq;
+        state.seq += 1;
+        state.queue.push(Entry {
+            key,
+            seq,
+            node: node.clone(),
+        });
+        Ok(Some(node))
+    }
+}
+
+#[test]
+fn wait_timeout_leaves_queue() {
+    let queue = Handoff::new(0, None);
+    assert!(!queue.try_wait());
+    let deadline = Instant::now() + Duration::from_millis(50);
+    assert!(!queue.wait_deadline(0, deadline));
+    assert_eq!(queue.queued(), 0);
+
+    queue.try_signal_n(1).unwrap();
+    assert!(queue.wait_deadline(0, deadline));
+}
+
+#[test]
+fn over_release() {
+    let queue = Handoff::new(u32::MAX - 1, None);
+    assert_eq!(queue.try_signal_n(2), Err(SemaphoreFull));
+    assert_eq!(queue.try_signal_n(1), Ok(()));
+    assert_eq!(queue.try_signal_n(1), Err(SemaphoreFull));
+}
+
+#[test]
+fn close_wakes_waiters() {
+    let queue = &Handoff::new(0, None);
+    thread::scope(|s| {
+        let waiters = (0..4)
+            .map(|priority| s.spawn(move || queue.wait(priority)))
+            .collect::<Vec<_>>();
+        while queue.queued() != 4 {
+            thread::yield_now();
+        }
+        queue.close();
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), Err(Closed));
+        }
+    });
+    assert_eq!(queue.wait(0), Err(Closed));
+    assert!(!queue.wait_deadline(0, Instant::now() + Duration::from_secs(10)));
+    // ресурс можно вернуть и после закрытия, но забрать его уже нельзя
+    queue.try_signal_n(1).unwrap();
+    assert!(!queue.try_wait());
+}
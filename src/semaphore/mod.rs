@@ -1,37 +1,306 @@
 use std::{
     sync::{
         atomic::{AtomicU32, AtomicU64, Ordering::*},
-        Condvar, Mutex,
+        Arc, Condvar, Mutex, MutexGuard,
     },
     thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
+pub mod bounded;
+#[cfg(test)]
+mod conformance;
+pub mod counting;
+pub mod fair;
+#[cfg(target_os = "linux")]
+mod futex;
+mod handoff;
 pub mod optimised;
+pub mod permit;
+pub mod priority;
+pub mod rate_limiter;
+#[cfg(target_os = "linux")]
+pub mod shared;
+#[cfg(feature = "stats")]
+pub mod stats;
+
+pub use bounded::BoundedSemaphore;
+pub use counting::CountingSemaphore;
+pub use fair::FairSemaphore;
+pub use permit::{OwnedSemaphorePermit, Release, SemaphorePermit};
+pub use priority::PrioritySemaphore;
+pub use rate_limiter::RateLimiter;
+#[cfg(target_os = "linux")]
+pub use shared::SharedSemaphore;
+
+/// Возвращается, когда ресурсов вернули больше, чем максимум семафора
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemaphoreFull;
+
+impl std::fmt::Display for SemaphoreFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Счётчик семафора достиг максимального значения")
+    }
+}
+
+impl std::error::Error for SemaphoreFull {}
+
+/// Возвращается из ожидания закрытого семафора
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closed;
+
+impl std::fmt::Display for Closed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Семафор закрыт")
+    }
+}
+
+impl std::error::Error for Closed {}
 
 pub struct Semaphore {
-    counter: Mutex<u32>,
+    state: Mutex<State>,
+    max: u32,
     is_wait: Condvar,
+    #[cfg(feature = "stats")]
+    stats: stats::WaitStats,
+}
+
+struct State {
+    counter: u32,
+    // ожидающий, которому не хватило ресурсов, резервирует семафор,
+    // и пока он не получит свои ресурсы, остальные их не забирают
+    reserved: bool,
+    closed: bool,
+    // сколько потоков сейчас ждут
+    waiters: u32,
 }
 
 impl Semaphore {
     pub const fn new(count_of_resurses: u32) -> Self {
+        Self::with_max(count_of_resurses, u32::MAX)
+    }
+    /// Семафор, в счётчике которого не может быть больше `max` ресурсов
+    pub const fn with_max(count_of_resurses: u32, max: u32) -> Self {
+        assert!(count_of_resurses <= max);
         Self {
-            counter: Mutex::new(count_of_resurses),
+            state: Mutex::new(State {
+                counter: count_of_resurses,
+                reserved: false,
+                closed: false,
+                waiters: 0,
+            }),
+            max,
             is_wait: Condvar::new(),
+            #[cfg(feature = "stats")]
+            stats: stats::WaitStats::new(),
         }
     }
     pub fn signal(&self) {
-        let mut counter = self.counter.lock().unwrap();
-        *counter += 1;
-        self.is_wait.notify_one();
+        self.signal_n(1);
     }
-    pub fn wait(&self) {
-        let mut counter = self.counter.lock().unwrap();
-        if *counter == 0 {
-            counter = self.is_wait.wait(counter).unwrap();
+    pub fn wait(&self) -> Result<(), Closed> {
+        self.wait_n(1)
+    }
+    /// Возвращает сразу `n` ресурсов. Работает и после закрытия семафора
+    pub fn signal_n(&self, n: u32) {
+        if let Err(e) = self.try_signal_n(n) {
+            panic!("{e}");
+        }
+    }
+    /// Как [`Self::signal`], но вместо паники сообщает о лишнем возврате
+    pub fn try_signal(&self) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(1)
+    }
+    pub fn try_signal_n(&self, n: u32) -> Result<(), SemaphoreFull> {
+        let mut state = self.state.lock().unwrap();
+        state.counter = match state.counter.checked_add(n) {
+            Some(counter) if counter <= self.max => counter,
+            _ => return Err(SemaphoreFull),
+        };
+        // ожидающие хотят разное количество ресурсов,
+        // поэтому разбуженный одиночка может оказаться не тем, кому их хватит
+        if state.reserved || n > 1 {
+            self.is_wait.notify_all();
+        } else {
+            self.is_wait.notify_one();
+        }
+        Ok(())
+    }
+    /// Забирает сразу `n` ресурсов.
+    ///
+    /// Большой запрос не голодает за потоком маленьких: если ресурсов не хватило,
+    /// то он резервирует семафор, и новые запросы ждут, пока он не будет выполнен
+    ///
+    /// # Panics
+    /// Если `n` больше максимума семафора: такой запрос не выполнится никогда,
+    /// а его резерв навсегда остановил бы всех остальных
+    pub fn wait_n(&self, n: u32) -> Result<(), Closed> {
+        assert!(
+            n <= self.max,
+            "Запрошено больше ресурсов, чем максимум семафора"
+        );
+        #[cfg(feature = "stats")]
+        let start = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let blocked = !state.closed && (state.reserved || state.counter < n);
+        if blocked {
+            state = self.block(state);
+        }
+        while state.reserved && !state.closed {
+            state = self.is_wait.wait(state).unwrap();
         }
-        *counter -= 1;
+        if state.counter < n && !state.closed {
+            state.reserved = true;
+            while state.counter < n && !state.closed {
+                state = self.is_wait.wait(state).unwrap();
+            }
+            state.reserved = false;
+            self.is_wait.notify_all();
+        }
+        if blocked {
+            state.waiters -= 1;
+        }
+        if state.closed {
+            return Err(Closed);
+        }
+        state.counter -= n;
+        #[cfg(feature = "stats")]
+        self.stats.record_wait(start.elapsed());
+        Ok(())
+    }
+    /// Учитывает поток, которому придётся ждать
+    fn block<'a>(&self, mut state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        state.waiters += 1;
+        #[cfg(feature = "stats")]
+        self.stats.record_waiters(state.waiters);
+        state
+    }
+    /// Забирает ресурс, только если он есть прямо сейчас
+    pub fn try_wait(&self) -> bool {
+        self.try_wait_n(1)
+    }
+    /// Забирает `n` ресурсов, только если они есть прямо сейчас
+    pub fn try_wait_n(&self, n: u32) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.reserved || state.closed || state.counter < n {
+            return false;
+        }
+        state.counter -= n;
+        true
+    }
+    /// Закрывает семафор: все текущие и будущие ожидания возвращают [`Closed`].
+    /// Уже выданные ресурсы по-прежнему можно вернуть
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.is_wait.notify_all();
+    }
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+    /// Забирает ресурс, который вернётся при удалении [`SemaphorePermit`]
+    pub fn acquire(&self) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.acquire_many(1)
+    }
+    pub fn acquire_many(&self, n: u32) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.wait_n(n)?;
+        Ok(SemaphorePermit::new(self, n))
+    }
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_, Self>> {
+        self.try_acquire_many(1)
+    }
+    pub fn try_acquire_many(&self, n: u32) -> Option<SemaphorePermit<'_, Self>> {
+        self.try_wait_n(n).then(|| SemaphorePermit::new(self, n))
+    }
+    /// Как [`Self::acquire`], но ресурс можно передать в другой поток
+    pub fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit<Self>, Closed> {
+        self.acquire_many_owned(1)
+    }
+    pub fn acquire_many_owned(
+        self: Arc<Self>,
+        n: u32,
+    ) -> Result<OwnedSemaphorePermit<Self>, Closed> {
+        self.wait_n(n)?;
+        Ok(OwnedSemaphorePermit::new(self, n))
+    }
+    /// Возвращает `false`, если ресурс не освободился за `timeout`
+    /// или семафор закрыт
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.wait_deadline(deadline),
+            None => self.wait().is_ok(),
+        }
+    }
+    /// Возвращает `false`, если ресурс не освободился к `deadline`
+    /// или семафор закрыт
+    pub fn wait_deadline(&self, deadline: Instant) -> bool {
+        #[cfg(feature = "stats")]
+        let start = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let blocked = !state.closed && (state.reserved || state.counter == 0);
+        if blocked {
+            state = self.block(state);
+        }
+        let taken = loop {
+            // как в `wait_n`, закрытие важнее свободных ресурсов
+            if state.closed {
+                break false;
+            }
+            if !state.reserved && state.counter != 0 {
+                break true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break false;
+            }
+            state = self.is_wait.wait_timeout(state, deadline - now).unwrap().0;
+        };
+        if blocked {
+            state.waiters -= 1;
+        }
+        if taken {
+            state.counter -= 1;
+            #[cfg(feature = "stats")]
+            self.stats.record_wait(start.elapsed());
+        }
+        taken
+    }
+    /// Количество свободных ресурсов
+    pub fn available(&self) -> u32 {
+        self.state.lock().unwrap().counter
+    }
+    /// Количество потоков, которые сейчас ждут ресурсы
+    pub fn waiters(&self) -> u32 {
+        self.state.lock().unwrap().waiters
+    }
+    /// Гистограмма времени успешных ожиданий и пик числа ожидающих
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> stats::Stats {
+        self.stats.snapshot()
+    }
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+}
+
+impl Release for Semaphore {
+    fn release(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(n)
+    }
+}
+
+impl CountingSemaphore for Semaphore {
+    fn wait(&self) -> Result<(), Closed> {
+        Semaphore::wait(self)
+    }
+    fn signal(&self) {
+        Semaphore::signal(self)
+    }
+    fn try_wait(&self) -> bool {
+        Semaphore::try_wait(self)
+    }
+    fn available(&self) -> u32 {
+        Semaphore::available(self)
     }
 }
 
@@ -44,10 +313,198 @@ fn wait_and_signal() {
     thread::scope(|s| {
         for _ in 0..count_of_resurses {
             s.spawn(|| {
-                semaphore.wait();
+                semaphore.wait().unwrap();
             });
         }
     });
-    assert_eq!(*semaphore.counter.lock().unwrap(), 0);
+    assert_eq!(semaphore.available(), 0);
     println!("time: {:?}", start.elapsed(),);
 }
+
+#[test]
+fn blocked_wait() {
+    let semaphore = Semaphore::new(0);
+
+    assert!(!semaphore.try_wait());
+    let start = Instant::now();
+    assert!(!semaphore.wait_timeout(Duration::from_millis(100)));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    semaphore.signal();
+    assert!(semaphore.try_wait());
+    assert!(!semaphore.wait_deadline(Instant::now()));
+}
+
+#[test]
+fn signal_wakes_timed_wait() {
+    let semaphore = Semaphore::new(0);
+    thread::scope(|s| {
+        let waiter = s.spawn(|| semaphore.wait_timeout(Duration::from_secs(10)));
+        semaphore.signal();
+        assert!(waiter.join().unwrap());
+    });
+    assert_eq!(semaphore.available(), 0);
+}
+
+#[test]
+fn wait_and_signal_n() {
+    let semaphore = Semaphore::new(10);
+    assert!(semaphore.try_wait_n(7));
+    assert!(!semaphore.try_wait_n(4));
+    thread::scope(|s| {
+        s.spawn(|| semaphore.wait_n(5).unwrap());
+        semaphore.signal_n(2);
+    });
+    assert_eq!(semaphore.available(), 0);
+}
+
+#[test]
+fn large_request_is_not_starved() {
+    let semaphore = Semaphore::new(4);
+    let done = std::sync::atomic::AtomicBool::new(false);
+    let (tx, rx) = std::sync::mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                while !done.load(Relaxed) {
+                    semaphore.wait().unwrap();
+                    thread::sleep(Duration::from_micros(100));
+                    semaphore.signal();
+                }
+            });
+        }
+        s.spawn(|| {
+            semaphore.wait_n(4).unwrap();
+            tx.send(()).unwrap();
+            semaphore.signal_n(4);
+        });
+        let res = rx.recv_timeout(Duration::from_secs(10));
+        done.store(true, Relaxed);
+        assert!(res.is_ok(), "большой запрос голодает");
+    });
+    assert_eq!(semaphore.available(), 4);
+}
+
+#[test]
+fn over_release() {
+    let semaphore = Semaphore::with_max(1, 2);
+    assert_eq!(semaphore.try_signal(), Ok(()));
+    assert_eq!(semaphore.try_signal(), Err(SemaphoreFull));
+    assert_eq!(semaphore.try_signal_n(3), Err(SemaphoreFull));
+    assert!(std::panic::catch_unwind(|| semaphore.signal()).is_err());
+    assert_eq!(semaphore.available(), 2);
+
+    let semaphore = Semaphore::new(u32::MAX);
+    assert_eq!(semaphore.try_signal(), Err(SemaphoreFull));
+}
+
+#[test]
+fn request_over_max() {
+    let semaphore = Semaphore::with_max(2, 2);
+    assert!(std::panic::catch_unwind(|| semaphore.wait_n(3)).is_err());
+    assert!(!semaphore.try_wait_n(3));
+    // резерв не остался висеть за невыполнимым запросом
+    assert_eq!(semaphore.wait_n(2), Ok(()));
+    assert_eq!(semaphore.waiters(), 0);
+}
+
+#[test]
+fn close_wakes_waiters() {
+    let semaphore = &Semaphore::new(1);
+    let permit = semaphore.acquire().unwrap();
+    let started = &crate::coordination::CountDownLatch::new(8);
+    thread::scope(|s| {
+        let waiters = (0..8)
+            .map(|i| {
+                s.spawn(move || {
+                    started.count_down();
+                    if i % 2 == 0 {
+                        semaphore.wait()
+                    } else {
+                        semaphore.wait_n(2)
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        started.wait();
+        semaphore.close();
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), Err(Closed));
+        }
+    });
+    assert!(semaphore.is_closed());
+    assert_eq!(semaphore.wait(), Err(Closed));
+    assert!(!semaphore.try_wait());
+    assert!(!semaphore.wait_timeout(Duration::from_secs(10)));
+
+    // выданный ресурс можно вернуть и после закрытия
+    drop(permit);
+    assert_eq!(semaphore.available(), 1);
+    // но свободный ресурс закрытого семафора уже никому не достаётся
+    assert!(!semaphore.wait_timeout(Duration::from_secs(10)));
+    assert_eq!(semaphore.available(), 1);
+}
+
+#[test]
+fn waiters_and_available() {
+    let semaphore = Semaphore::new(1);
+    assert_eq!((semaphore.available(), semaphore.waiters()), (1, 0));
+    let permit = semaphore.acquire().unwrap();
+    assert_eq!(semaphore.available(), 0);
+    thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| semaphore.wait().unwrap());
+        }
+        s.spawn(|| semaphore.wait_timeout(Duration::from_secs(10)));
+        while semaphore.waiters() != 4 {
+            thread::yield_now();
+        }
+        drop(permit);
+        semaphore.signal_n(3);
+    });
+    assert_eq!((semaphore.available(), semaphore.waiters()), (0, 0));
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn wait_stats() {
+    fn contend(wait: impl Fn() + Sync, signal: impl Fn() + Sync, waiters: impl Fn() -> u32) {
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(&wait);
+            }
+            while waiters() != 4 {
+                thread::yield_now();
+            }
+            thread::sleep(Duration::from_millis(5));
+            (0..4).for_each(|_| signal());
+        });
+    }
+
+    let semaphore = Semaphore::new(1);
+    semaphore.wait().unwrap();
+    contend(
+        || semaphore.wait().unwrap(),
+        || semaphore.signal(),
+        || semaphore.waiters(),
+    );
+    let stats = semaphore.stats();
+    assert_eq!(stats.waits(), 5);
+    assert_eq!(stats.peak_waiters, 4);
+    // ожидавшие дольше 4мс не попадают в корзины до 4096мкс
+    assert_eq!(stats.histogram[13..].iter().sum::<u64>(), 4);
+    semaphore.reset_stats();
+    assert_eq!(semaphore.stats().waits(), 0);
+
+    let semaphore = optimised::Semaphore::new(1);
+    semaphore.wait().unwrap();
+    contend(
+        || semaphore.wait().unwrap(),
+        || semaphore.signal(),
+        || semaphore.waiters(),
+    );
+    let stats = semaphore.stats();
+    assert_eq!(stats.waits(), 5);
+    assert_eq!(stats.peak_waiters, 4);
+    assert_eq!(stats.histogram[13..].iter().sum::<u64>(), 4);
+}
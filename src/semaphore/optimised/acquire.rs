@@ -0,0 +1,410 @@
+use super::*;
+use std::{
+    cell::UnsafeCell,
+    future::Future,
+    pin::Pin,
+    sync::atomic::AtomicU8,
+    task::{Context, Poll, Waker},
+};
+
+const WAITING: u8 = 0;
+const NOTIFIED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+// состояния `AtomicWaker`
+const IDLE: u8 = 0;
+const REGISTERING: u8 = 1;
+const WAKING: u8 = 2;
+
+/// Waker, который future заменяет, а уведомитель забирает без блокировки.
+///
+/// Пока идёт замена, уведомитель только ставит `WAKING`,
+/// и тогда новый waker будит сам заменявший
+struct AtomicWaker {
+    state: AtomicU8,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    fn new(waker: Waker) -> Self {
+        Self {
+            state: AtomicU8::new(IDLE),
+            waker: UnsafeCell::new(Some(waker)),
+        }
+    }
+    /// Вызывается только владельцем future, поэтому замены не пересекаются
+    fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(IDLE, REGISTERING, Acquire, Acquire)
+        {
+            Ok(_) => {
+                // с `REGISTERING` к waker никто кроме нас не обращается
+                unsafe { *self.waker.get() = Some(waker.clone()) };
+                if self
+                    .state
+                    .compare_exchange(REGISTERING, IDLE, AcqRel, Acquire)
+                    .is_err()
+                {
+                    // пока меняли, пришло пробуждение
+                    let waker = unsafe { (*self.waker.get()).take() };
+                    self.state.store(IDLE, Release);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            // уведомитель сейчас забирает старый waker
+            Err(_) => waker.wake_by_ref(),
+        }
+    }
+    fn wake(&self) {
+        if self.state.fetch_or(WAKING, AcqRel) == IDLE {
+            let waker = unsafe { (*self.waker.get()).take() };
+            self.state.fetch_and(!WAKING, Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Асинхронный ожидающий в очереди семафора
+pub(super) struct Waiter {
+    state: AtomicU8,
+    waker: AtomicWaker,
+}
+
+impl Waiter {
+    fn new(waker: Waker) -> Self {
+        Self {
+            state: AtomicU8::new(WAITING),
+            waker: AtomicWaker::new(waker),
+        }
+    }
+    /// Будит ожидающего, если он ещё не ушёл из очереди
+    fn notify(&self) -> bool {
+        if self
+            .state
+            .compare_exchange(WAITING, NOTIFIED, AcqRel, Acquire)
+            .is_err()
+        {
+            return false;
+        }
+        self.waker.wake();
+        true
+    }
+}
+
+impl Semaphore {
+    /// Асинхронно забирает ресурс, не блокируя поток, или возвращает [`Closed`].
+    ///
+    /// Счётчик общий с [`Semaphore::wait`], поэтому синхронные
+    /// и асинхронные пользователи могут работать с одним семафором
+    pub fn acquire_async(&self) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            waiter: None,
+            blocked: false,
+            #[cfg(feature = "stats")]
+            start: std::time::Instant::now(),
+        }
+    }
+    /// Будит до `n` асинхронных ожидающих, пропуская ушедших из очереди
+    pub(super) fn notify_async(&self, mut n: u32) {
+        while n != 0 {
+            let Some(waiter) = self.waiters.pop() else {
+                break;
+            };
+            if waiter.notify() {
+                n -= 1;
+            } else {
+                self.cancelled.fetch_sub(1, Relaxed);
+            }
+        }
+    }
+    pub(super) fn notify_async_all(&self) {
+        while let Some(waiter) = self.waiters.pop() {
+            if !waiter.notify() {
+                self.cancelled.fetch_sub(1, Relaxed);
+            }
+        }
+    }
+    /// Учитывает ушедшего из очереди. Когда ушедших больше половины очереди,
+    /// она вычищается: оставшиеся просыпаются и встают в неё заново,
+    /// поэтому очередь не растёт из-за отменённых future
+    fn cancel_async(&self) {
+        // уведомитель мог вычесть этого ожидающего раньше, чем мы его прибавили
+        let cancelled = self.cancelled.fetch_add(1, Relaxed).wrapping_add(1);
+        if cancelled as usize > self.waiters.len() / 2 {
+            self.notify_async_all();
+        }
+    }
+}
+
+/// Future из [`Semaphore::acquire_async`].
+///
+/// Если его удалить после того, как его разбудили, то пробуждение
+/// передаётся следующему в очереди, поэтому ни ресурс, ни пробуждение не теряются
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    waiter: Option<Arc<Waiter>>,
+    // учтён ли в `Semaphore::waiters`
+    blocked: bool,
+    #[cfg(feature = "stats")]
+    start: std::time::Instant,
+}
+
+impl Acquire<'_> {
+    /// Уходит из очереди, и если пробуждение уже было получено,
+    /// то передаёт его следующему
+    fn leave(&mut self) {
+        if std::mem::take(&mut self.blocked) {
+            self.semaphore.unblock();
+        }
+        if let Some(waiter) = self.waiter.take() {
+            match waiter
+                .state
+                .compare_exchange(WAITING, CANCELLED, AcqRel, Acquire)
+            {
+                Ok(_) => self.semaphore.cancel_async(),
+                Err(_) => self.semaphore.notify_async(1),
+            }
+        }
+    }
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Result<SemaphorePermit<'a, Semaphore>, Closed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let semaphore = this.semaphore;
+
+        let notified = this
+            .waiter
+            .as_ref()
+            .is_some_and(|w| w.state.load(Acquire) == NOTIFIED);
+        if semaphore.try_wait() {
+            if notified {
+                // пробуждение использовано по назначению
+                this.waiter = None;
+            }
+            this.leave();
+            #[cfg(feature = "stats")]
+            semaphore.stats.record_wait(this.start.elapsed());
+            return Poll::Ready(Ok(SemaphorePermit::new(semaphore, 1)));
+        }
+        if semaphore.is_closed() {
+            this.leave();
+            return Poll::Ready(Err(Closed));
+        }
+
+        match &this.waiter {
+            Some(waiter) if !notified => {
+                waiter.waker.register(cx.waker());
+                // уведомитель мог разбудить старый waker
+                if waiter.state.load(Acquire) == NOTIFIED {
+                    cx.waker().wake_by_ref();
+                }
+            }
+            // ресурс, о котором нас уведомили, забрал кто-то другой,
+            // поэтому встаём в очередь заново
+            _ => {
+                let waiter = Arc::new(Waiter::new(cx.waker().clone()));
+                semaphore.waiters.push(waiter.clone());
+                this.waiter = Some(waiter);
+            }
+        }
+
+        // ресурс мог освободиться до того, как мы встали в очередь
+        if semaphore.try_wait() {
+            this.leave();
+            #[cfg(feature = "stats")]
+            semaphore.stats.record_wait(this.start.elapsed());
+            return Poll::Ready(Ok(SemaphorePermit::new(semaphore, 1)));
+        }
+        // `close` мог пройти по очереди до того, как мы в неё встали
+        if semaphore.is_closed() {
+            this.leave();
+            return Poll::Ready(Err(Closed));
+        }
+        if !this.blocked {
+            this.blocked = true;
+            semaphore.block();
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+#[cfg(test)]
+fn block_on<F: Future>(future: F) -> F::Output {
+    use std::{task::Wake, thread};
+
+    struct Unpark(thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(res) = future.as_mut().poll(&mut cx) {
+            return res;
+        }
+        thread::park();
+    }
+}
+
+#[cfg(test)]
+#[derive(Default)]
+pub(super) struct Flag(std::sync::atomic::AtomicBool);
+
+#[cfg(test)]
+impl std::task::Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Relaxed);
+    }
+}
+
+#[test]
+fn acquire_async() {
+    use std::thread;
+
+    let semaphore = Semaphore::new(1);
+    let permit = block_on(semaphore.acquire_async()).unwrap();
+    assert!(!semaphore.try_wait());
+
+    let started = crate::coordination::CountDownLatch::new(1);
+    thread::scope(|s| {
+        let waiter = s.spawn(|| {
+            started.count_down();
+            block_on(semaphore.acquire_async()).unwrap().forget()
+        });
+        started.wait();
+        drop(permit);
+        waiter.join().unwrap();
+    });
+    assert_eq!(semaphore.available(), 0);
+}
+
+#[test]
+fn dropped_future_forwards_wakeup() {
+    let semaphore = Semaphore::new(0);
+    let (first_woken, second_woken) = (Arc::new(Flag::default()), Arc::new(Flag::default()));
+    let first_waker = Waker::from(first_woken.clone());
+    let second_waker = Waker::from(second_woken.clone());
+
+    let mut first = Box::pin(semaphore.acquire_async());
+    let mut second = Box::pin(semaphore.acquire_async());
+    assert!(first
+        .as_mut()
+        .poll(&mut Context::from_waker(&first_waker))
+        .is_pending());
+    assert!(second
+        .as_mut()
+        .poll(&mut Context::from_waker(&second_waker))
+        .is_pending());
+
+    // пробуждение достаётся первому, но он удаляется, не забрав ресурс
+    semaphore.signal();
+    assert!(first_woken.0.load(Relaxed));
+    assert!(!second_woken.0.load(Relaxed));
+    drop(first);
+    assert!(second_woken.0.load(Relaxed), "пробуждение потерялось");
+
+    let Poll::Ready(Ok(permit)) = second
+        .as_mut()
+        .poll(&mut Context::from_waker(&second_waker))
+    else {
+        panic!("ресурс потерялся");
+    };
+    permit.forget();
+    assert_eq!(semaphore.available(), 0);
+    assert!(semaphore.waiters.is_empty());
+}
+
+#[test]
+fn mixed_sync_and_async() {
+    use std::thread;
+
+    let semaphore = &Semaphore::new(4);
+    let inside = AtomicU32::new(0);
+    let critical_section = || {
+        assert!(inside.fetch_add(1, Relaxed) < 4);
+        thread::yield_now();
+        inside.fetch_sub(1, Relaxed);
+    };
+    thread::scope(|s| {
+        for i in 0..32 {
+            s.spawn(move || {
+                for _ in 0..200 {
+                    if i % 2 == 0 {
+                        let _permit = block_on(semaphore.acquire_async()).unwrap();
+                        critical_section();
+                    } else {
+                        let _permit = semaphore.acquire().unwrap();
+                        critical_section();
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(semaphore.available(), 4);
+}
+
+#[test]
+fn close_wakes_async_waiters() {
+    let semaphore = Semaphore::new(0);
+    let woken = Arc::new(Flag::default());
+    let waker = Waker::from(woken.clone());
+
+    let mut acquire = Box::pin(semaphore.acquire_async());
+    assert!(acquire
+        .as_mut()
+        .poll(&mut Context::from_waker(&waker))
+        .is_pending());
+    semaphore.close();
+    assert!(woken.0.load(Relaxed));
+    assert!(matches!(
+        acquire.as_mut().poll(&mut Context::from_waker(&waker)),
+        Poll::Ready(Err(Closed))
+    ));
+    assert!(block_on(semaphore.acquire_async()).is_err());
+}
+
+#[test]
+fn cancelled_futures_do_not_pile_up() {
+    let semaphore = Semaphore::new(0);
+    let woken = Arc::new(Flag::default());
+    let waker = Waker::from(woken.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut live = Box::pin(semaphore.acquire_async());
+    assert!(live.as_mut().poll(&mut cx).is_pending());
+    for _ in 0..1_000 {
+        let mut cancelled = Box::pin(semaphore.acquire_async());
+        assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+    }
+    assert!(semaphore.waiters.len() <= 2);
+
+    // вычищая очередь, живого будят, и он встаёт в неё заново
+    assert!(woken.0.load(Relaxed));
+    assert!(live.as_mut().poll(&mut cx).is_pending());
+    semaphore.signal();
+    assert!(live.as_mut().poll(&mut cx).is_ready());
+    assert_eq!(semaphore.waiters(), 0);
+}
@@ -0,0 +1,645 @@
+#[cfg(target_os = "linux")]
+use super::futex;
+use super::{
+    counting::CountingSemaphore,
+    permit::{OwnedSemaphorePermit, Release, SemaphorePermit},
+    Closed, SemaphoreFull,
+};
+use acquire::Waiter;
+use atomic_wait::{wait, wake_all, wake_one};
+use crossbeam::queue::SegQueue;
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering::*},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+pub struct Semaphore {
+    // старший бит это признак закрытия семафора, остальные это количество ресурсов.
+    // Признак лежит в том же слове, на котором спят ожидающие, поэтому `close`
+    // не может потерять пробуждение
+    counter: AtomicU32,
+    max: u32,
+    // 1, если ожидающему не хватило ресурсов и он зарезервировал семафор:
+    // пока он не получит свои ресурсы, остальные их не забирают.
+    // После закрытия здесь навсегда `CLOSED_RESERVED`
+    reserved: AtomicU32,
+    // асинхронные ожидающие из `acquire_async`
+    waiters: SegQueue<Arc<Waiter>>,
+    // сколько из них уже ушли, но ещё лежат в очереди
+    cancelled: AtomicU32,
+    // сколько потоков и future сейчас ждут
+    waiting: AtomicU32,
+    #[cfg(feature = "stats")]
+    stats: super::stats::WaitStats,
+}
+
+const CLOSED: u32 = 1 << 31;
+const CLOSED_RESERVED: u32 = 2;
+
+pub mod acquire;
+
+pub use acquire::Acquire;
+
+/// Вместо того чтобы блокировать counter на всем пути до разблокировки,
+/// мы выполняем операции атомарно солгалсуясь с другими потоками
+impl Semaphore {
+    pub const fn new(count_of_resurses: u32) -> Self {
+        Self::with_max(count_of_resurses, CLOSED - 1)
+    }
+    /// Семафор, в счётчике которого не может быть больше `max` ресурсов
+    pub const fn with_max(count_of_resurses: u32, max: u32) -> Self {
+        assert!(count_of_resurses <= max && max < CLOSED);
+        Self {
+            counter: AtomicU32::new(count_of_resurses),
+            max,
+            reserved: AtomicU32::new(0),
+            waiters: SegQueue::new(),
+            cancelled: AtomicU32::new(0),
+            waiting: AtomicU32::new(0),
+            #[cfg(feature = "stats")]
+            stats: super::stats::WaitStats::new(),
+        }
+    }
+    pub fn signal(&self) {
+        self.signal_n(1);
+    }
+    pub fn wait(&self) -> Result<(), Closed> {
+        self.wait_n(1)
+    }
+    /// Возвращает сразу `n` ресурсов. Работает и после закрытия семафора
+    pub fn signal_n(&self, n: u32) {
+        if let Err(e) = self.try_signal_n(n) {
+            panic!("{e}");
+        }
+    }
+    /// Как [`Self::signal`], но вместо паники сообщает о лишнем возврате
+    pub fn try_signal(&self) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(1)
+    }
+    pub fn try_signal_n(&self, n: u32) -> Result<(), SemaphoreFull> {
+        let mut c = self.counter.load(Relaxed);
+        loop {
+            let new = match (c & !CLOSED).checked_add(n) {
+                Some(new) if new <= self.max => new | (c & CLOSED),
+                _ => return Err(SemaphoreFull),
+            };
+            match self.counter.compare_exchange(c, new, Release, Relaxed) {
+                Ok(_) => break,
+                Err(e) => c = e,
+            }
+        }
+        // на счётчике спит только зарезервировавший семафор, остальные ждут резерв
+        wake_one(&self.counter);
+        self.notify_async(n);
+        Ok(())
+    }
+    /// Забирает сразу `n` ресурсов.
+    ///
+    /// Большой запрос не голодает за потоком маленьких: если ресурсов не хватило,
+    /// то он резервирует семафор, и новые запросы ждут, пока он не будет выполнен
+    ///
+    /// # Panics
+    /// Если `n` больше максимума семафора: такой запрос не выполнится никогда,
+    /// а его резерв навсегда остановил бы всех остальных
+    pub fn wait_n(&self, n: u32) -> Result<(), Closed> {
+        assert!(
+            n <= self.max,
+            "Запрошено больше ресурсов, чем максимум семафора"
+        );
+        if self.try_wait_n(n) {
+            #[cfg(feature = "stats")]
+            self.stats.record_wait(Duration::ZERO);
+            return Ok(());
+        }
+        #[cfg(feature = "stats")]
+        let start = Instant::now();
+        self.block();
+        let res = self.wait_n_slow(n);
+        self.unblock();
+        #[cfg(feature = "stats")]
+        if res.is_ok() {
+            self.stats.record_wait(start.elapsed());
+        }
+        res
+    }
+    fn wait_n_slow(&self, n: u32) -> Result<(), Closed> {
+        loop {
+            match self.reserved.load(Acquire) {
+                CLOSED_RESERVED => return Err(Closed),
+                1 => {
+                    wait(&self.reserved, 1);
+                    continue;
+                }
+                _ => {}
+            }
+            if self.take(n)? {
+                return Ok(());
+            }
+            if self
+                .reserved
+                .compare_exchange(0, 1, Acquire, Relaxed)
+                .is_ok()
+            {
+                break;
+            }
+        }
+        let res = loop {
+            match self.take(n) {
+                Ok(true) => break Ok(()),
+                Ok(false) => {}
+                Err(closed) => break Err(closed),
+            }
+            let c = self.counter.load(Relaxed);
+            if c < n {
+                wait(&self.counter, c);
+            }
+        };
+        self.unreserve();
+        res
+    }
+    /// Снимает резерв и будит всех, кто его ждал
+    fn unreserve(&self) {
+        // после закрытия резерв уже заменён на `CLOSED_RESERVED`
+        if self
+            .reserved
+            .compare_exchange(1, 0, Release, Relaxed)
+            .is_ok()
+        {
+            wake_all(&self.reserved);
+            // асинхронные ожидающие не могли забрать ресурсы, пока семафор был зарезервирован
+            self.notify_async_all();
+        }
+    }
+    /// Забирает ресурс, только если он есть прямо сейчас
+    pub fn try_wait(&self) -> bool {
+        self.try_wait_n(1)
+    }
+    /// Забирает `n` ресурсов, только если они есть прямо сейчас
+    pub fn try_wait_n(&self, n: u32) -> bool {
+        self.reserved.load(Acquire) == 0 && self.take(n) == Ok(true)
+    }
+    /// Уменьшает счётчик только с достаточного значения одним CAS:
+    /// проверка и списание атомарны, поэтому два потока не могут оба увидеть
+    /// последний ресурс и увести счётчик в переполнение
+    fn take(&self, n: u32) -> Result<bool, Closed> {
+        let mut c = self.counter.load(Relaxed);
+        while c >= n {
+            if c & CLOSED != 0 {
+                return Err(Closed);
+            }
+            match self
+                .counter
+                .compare_exchange_weak(c, c - n, Acquire, Relaxed)
+            {
+                Ok(_) => return Ok(true),
+                Err(e) => c = e,
+            }
+        }
+        Ok(false)
+    }
+    /// Закрывает семафор: все текущие и будущие ожидания возвращают [`Closed`].
+    /// Уже выданные ресурсы по-прежнему можно вернуть
+    pub fn close(&self) {
+        self.counter.fetch_or(CLOSED, Release);
+        self.reserved.store(CLOSED_RESERVED, Release);
+        wake_all(&self.counter);
+        wake_all(&self.reserved);
+        self.notify_async_all();
+    }
+    pub fn is_closed(&self) -> bool {
+        self.counter.load(Acquire) & CLOSED != 0
+    }
+    /// Забирает ресурс, который вернётся при удалении [`SemaphorePermit`]
+    pub fn acquire(&self) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.acquire_many(1)
+    }
+    pub fn acquire_many(&self, n: u32) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.wait_n(n)?;
+        Ok(SemaphorePermit::new(self, n))
+    }
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_, Self>> {
+        self.try_acquire_many(1)
+    }
+    pub fn try_acquire_many(&self, n: u32) -> Option<SemaphorePermit<'_, Self>> {
+        self.try_wait_n(n).then(|| SemaphorePermit::new(self, n))
+    }
+    /// Как [`Self::acquire`], но ресурс можно передать в другой поток
+    pub fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit<Self>, Closed> {
+        self.acquire_many_owned(1)
+    }
+    pub fn acquire_many_owned(
+        self: Arc<Self>,
+        n: u32,
+    ) -> Result<OwnedSemaphorePermit<Self>, Closed> {
+        self.wait_n(n)?;
+        Ok(OwnedSemaphorePermit::new(self, n))
+    }
+    /// Возвращает `false`, если ресурс не освободился за `timeout`
+    /// или семафор закрыт
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.wait_deadline(deadline),
+            None => self.wait().is_ok(),
+        }
+    }
+    /// Возвращает `false`, если ресурс не освободился к `deadline`
+    /// или семафор закрыт
+    pub fn wait_deadline(&self, deadline: Instant) -> bool {
+        if self.try_wait() {
+            #[cfg(feature = "stats")]
+            self.stats.record_wait(Duration::ZERO);
+            return true;
+        }
+        #[cfg(feature = "stats")]
+        let start = Instant::now();
+        self.block();
+        let taken = self.wait_deadline_slow(deadline);
+        self.unblock();
+        #[cfg(feature = "stats")]
+        if taken {
+            self.stats.record_wait(start.elapsed());
+        }
+        taken
+    }
+    /// Ждёт так же, как [`Self::wait_n`], но с таймаутом, которого нет в atomic-wait:
+    /// сначала чужой резерв, потом резервирует семафор и спит на счётчике сам
+    #[cfg(target_os = "linux")]
+    fn wait_deadline_slow(&self, deadline: Instant) -> bool {
+        loop {
+            match self.reserved.load(Acquire) {
+                CLOSED_RESERVED => return false,
+                1 => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    futex::wait_private(&self.reserved, 1, Some(deadline - now));
+                    continue;
+                }
+                _ => {}
+            }
+            match self.take(1) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(Closed) => return false,
+            }
+            if self
+                .reserved
+                .compare_exchange(0, 1, Acquire, Relaxed)
+                .is_ok()
+            {
+                break;
+            }
+        }
+        let taken = loop {
+            match self.take(1) {
+                Ok(true) => break true,
+                Ok(false) => {}
+                Err(Closed) => break false,
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break false;
+            }
+            let c = self.counter.load(Relaxed);
+            if c & !CLOSED == 0 {
+                futex::wait_private(&self.counter, c, Some(deadline - now));
+            }
+        };
+        self.unreserve();
+        taken
+    }
+    /// Без futex ограниченное по времени ожидание опрашивает счётчик,
+    /// сначала крутясь, а потом засыпая с нарастающей паузой
+    #[cfg(not(target_os = "linux"))]
+    fn wait_deadline_slow(&self, deadline: Instant) -> bool {
+        let mut backoff = Duration::from_micros(1);
+        loop {
+            if self.try_wait() {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline || self.is_closed() {
+                return false;
+            }
+            if backoff < Duration::from_micros(64) {
+                for _ in 0..backoff.as_micros() {
+                    std::hint::spin_loop();
+                }
+            } else {
+                std::thread::sleep(backoff.min(deadline - now));
+            }
+            backoff = (backoff * 2).min(Duration::from_millis(1));
+        }
+    }
+    /// Количество свободных ресурсов
+    pub fn available(&self) -> u32 {
+        self.counter.load(Relaxed) & !CLOSED
+    }
+    /// Количество потоков и future, которые сейчас ждут ресурсы
+    pub fn waiters(&self) -> u32 {
+        self.waiting.load(Relaxed)
+    }
+    /// Гистограмма времени успешных ожиданий и пик числа ожидающих
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> super::stats::Stats {
+        self.stats.snapshot()
+    }
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+    /// Учитывает ожидающего, которому не хватило ресурсов сразу
+    fn block(&self) {
+        #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+        let waiting = self.waiting.fetch_add(1, Relaxed) + 1;
+        #[cfg(feature = "stats")]
+        self.stats.record_waiters(waiting);
+    }
+    fn unblock(&self) {
+        self.waiting.fetch_sub(1, Relaxed);
+    }
+}
+
+impl Release for Semaphore {
+    fn release(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(n)
+    }
+}
+
+impl CountingSemaphore for Semaphore {
+    fn wait(&self) -> Result<(), Closed> {
+        Semaphore::wait(self)
+    }
+    fn signal(&self) {
+        Semaphore::signal(self)
+    }
+    fn try_wait(&self) -> bool {
+        Semaphore::try_wait(self)
+    }
+    fn available(&self) -> u32 {
+        Semaphore::available(self)
+    }
+}
+
+#[test]
+fn wait_and_signal() {
+    use std::thread;
+
+    let count_of_resurses = 50_000;
+    let mut semaphore = Semaphore::new(count_of_resurses);
+
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..count_of_resurses {
+            s.spawn(|| {
+                semaphore.wait().unwrap();
+            });
+        }
+    });
+    assert_eq!(*semaphore.counter.get_mut(), 0);
+    println!("time: {:?}", start.elapsed(),);
+}
+
+// вариации когда может быть блокировка
+#[test]
+fn blocked_wait() {
+    let semaphore = Semaphore::new(0);
+
+    assert!(!semaphore.try_wait());
+    let start = Instant::now();
+    assert!(!semaphore.wait_timeout(Duration::from_millis(100)));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    semaphore.signal();
+    assert!(semaphore.try_wait());
+    assert!(!semaphore.wait_deadline(Instant::now()));
+}
+#[test]
+fn signal_wakes_timed_wait() {
+    use std::thread;
+
+    let semaphore = Semaphore::new(0);
+    thread::scope(|s| {
+        let waiter = s.spawn(|| semaphore.wait_timeout(Duration::from_secs(10)));
+        semaphore.signal();
+        assert!(waiter.join().unwrap());
+    });
+    assert_eq!(semaphore.available(), 0);
+}
+#[test]
+fn timed_waits_behind_reservation() {
+    use std::thread;
+
+    // `signal` будит одного, и ни одно пробуждение не должно потеряться
+    // между ожидающими с таймаутом и большим запросом
+    let semaphore = Semaphore::new(0);
+    thread::scope(|s| {
+        let timed = (0..4)
+            .map(|_| s.spawn(|| semaphore.wait_timeout(Duration::from_secs(10))))
+            .collect::<Vec<_>>();
+        let large = s.spawn(|| semaphore.wait_n(2));
+        for _ in 0..6 {
+            semaphore.signal();
+        }
+        assert_eq!(large.join().unwrap(), Ok(()));
+        for waiter in timed {
+            assert!(waiter.join().unwrap());
+        }
+    });
+    assert_eq!(semaphore.available(), 0);
+}
+#[test]
+fn wait_and_signal_n() {
+    use std::thread;
+
+    let semaphore = Semaphore::new(10);
+    assert!(semaphore.try_wait_n(7));
+    assert!(!semaphore.try_wait_n(4));
+    thread::scope(|s| {
+        s.spawn(|| semaphore.wait_n(5).unwrap());
+        semaphore.signal_n(2);
+    });
+    assert_eq!(semaphore.available(), 0);
+}
+#[test]
+fn large_request_is_not_starved() {
+    use std::thread;
+
+    let semaphore = Semaphore::new(4);
+    let done = std::sync::atomic::AtomicBool::new(false);
+    let (tx, rx) = std::sync::mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                while !done.load(Relaxed) {
+                    semaphore.wait().unwrap();
+                    thread::sleep(Duration::from_micros(100));
+                    semaphore.signal();
+                }
+            });
+        }
+        s.spawn(|| {
+            semaphore.wait_n(4).unwrap();
+            tx.send(()).unwrap();
+            semaphore.signal_n(4);
+        });
+        let res = rx.recv_timeout(Duration::from_secs(10));
+        done.store(true, Relaxed);
+        assert!(res.is_ok(), "большой запрос голодает");
+    });
+    assert_eq!(semaphore.available(), 4);
+}
+#[test]
+fn blocked_signal() {
+    let semaphore = Semaphore::new(0);
+    semaphore.counter.store(u32::MAX, Relaxed);
+    assert_eq!(semaphore.try_signal(), Err(SemaphoreFull));
+    assert!(std::panic::catch_unwind(|| semaphore.signal()).is_err());
+    assert_eq!(semaphore.counter.load(Relaxed), u32::MAX);
+}
+#[test]
+fn over_release() {
+    let semaphore = Semaphore::with_max(1, 2);
+    assert_eq!(semaphore.try_signal(), Ok(()));
+    assert_eq!(semaphore.try_signal(), Err(SemaphoreFull));
+    assert_eq!(semaphore.try_signal_n(3), Err(SemaphoreFull));
+    assert_eq!(semaphore.available(), 2);
+}
+#[test]
+fn request_over_max() {
+    let semaphore = Semaphore::with_max(2, 2);
+    assert!(std::panic::catch_unwind(|| semaphore.wait_n(3)).is_err());
+    assert!(!semaphore.try_wait_n(3));
+    // резерв не остался висеть за невыполнимым запросом
+    assert_eq!(semaphore.wait_n(2), Ok(()));
+    assert_eq!(semaphore.waiters(), 0);
+}
+#[test]
+fn close_wakes_waiters() {
+    use std::thread;
+
+    let semaphore = &Semaphore::new(1);
+    let permit = semaphore.acquire().unwrap();
+    let started = &crate::coordination::CountDownLatch::new(8);
+    thread::scope(|s| {
+        let waiters = (0..8)
+            .map(|i| {
+                s.spawn(move || {
+                    started.count_down();
+                    if i % 2 == 0 {
+                        semaphore.wait()
+                    } else {
+                        semaphore.wait_n(2)
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        started.wait();
+        semaphore.close();
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), Err(Closed));
+        }
+    });
+    assert!(semaphore.is_closed());
+    assert_eq!(semaphore.wait(), Err(Closed));
+    assert!(!semaphore.try_wait());
+    assert!(!semaphore.wait_timeout(Duration::from_secs(10)));
+
+    // выданный ресурс можно вернуть и после закрытия
+    drop(permit);
+    assert_eq!(semaphore.available(), 1);
+}
+
+#[test]
+fn critical_section_never_oversubscribed() {
+    use std::{sync::atomic::AtomicBool, thread};
+
+    const PERMITS: u32 = 3;
+
+    let semaphore = Semaphore::new(PERMITS);
+    let inside = AtomicU32::new(0);
+    let max_inside = AtomicU32::new(0);
+    let stop = AtomicBool::new(false);
+    thread::scope(|s| {
+        // останавливает проверку и при панике рабочего потока,
+        // иначе scope ждал бы её вечно вместо того, чтобы сообщить об ошибке
+        struct Stop<'a>(&'a AtomicBool);
+        impl Drop for Stop<'_> {
+            fn drop(&mut self) {
+                self.0.store(true, Relaxed);
+            }
+        }
+        let _stop = Stop(&stop);
+        // счётчик не должен выходить за начальное значение даже на мгновение
+        s.spawn(|| {
+            while !stop.load(Relaxed) {
+                assert!(semaphore.counter.load(Relaxed) <= PERMITS);
+            }
+        });
+        let workers = (0..16u32)
+            .map(|i| {
+                let (semaphore, inside, max_inside) = (&semaphore, &inside, &max_inside);
+                s.spawn(move || {
+                    for _ in 0..2_000 {
+                        let n = i % 2 + 1;
+                        let taken = match i % 4 {
+                            0 | 1 => semaphore.wait_n(n).is_ok(),
+                            2 => semaphore.try_wait_n(n),
+                            _ => semaphore.wait_timeout(Duration::from_millis(1)),
+                        };
+                        if !taken {
+                            continue;
+                        }
+                        let n = if i % 4 == 3 { 1 } else { n };
+                        let now = inside.fetch_add(n, AcqRel) + n;
+                        max_inside.fetch_max(now, Relaxed);
+                        thread::yield_now();
+                        inside.fetch_sub(n, AcqRel);
+                        semaphore.signal_n(n);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers.into_iter().for_each(|w| w.join().unwrap());
+    });
+    assert!(max_inside.into_inner() <= PERMITS);
+    assert_eq!(semaphore.counter.into_inner(), PERMITS);
+}
+
+#[test]
+fn waiters_and_available() {
+    use std::thread;
+
+    let semaphore = Semaphore::new(1);
+    assert_eq!((semaphore.available(), semaphore.waiters()), (1, 0));
+    let permit = semaphore.acquire().unwrap();
+    assert_eq!(semaphore.available(), 0);
+
+    let waker = std::task::Waker::from(Arc::new(acquire::Flag::default()));
+    let mut pending = Box::pin(semaphore.acquire_async());
+    assert!(std::future::Future::poll(
+        pending.as_mut(),
+        &mut std::task::Context::from_waker(&waker)
+    )
+    .is_pending());
+    assert_eq!(semaphore.waiters(), 1);
+    drop(pending);
+    assert_eq!(semaphore.waiters(), 0);
+
+    thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| semaphore.wait().unwrap());
+        }
+        s.spawn(|| semaphore.wait_timeout(Duration::from_secs(10)));
+        while semaphore.waiters() != 4 {
+            thread::yield_now();
+        }
+        drop(permit);
+        semaphore.signal_n(3);
+    });
+    assert_eq!((semaphore.available(), semaphore.waiters()), (0, 0));
+}
@@ -0,0 +1,131 @@
+use super::SemaphoreFull;
+use std::sync::Arc;
+
+/// Семафор, которому можно вернуть ресурсы.
+///
+/// Вызывается из `Drop`, поэтому лишний возврат это ошибка, а не паника:
+/// паника во время раскрутки стека аварийно завершила бы процесс
+pub trait Release {
+    fn release(&self, n: u32) -> Result<(), SemaphoreFull>;
+}
+
+/// Ресурсы семафора, которые возвращаются при удалении,
+/// даже если поток запаниковал или вышел раньше времени
+#[must_use]
+pub struct SemaphorePermit<'a, S: Release + ?Sized> {
+    semaphore: &'a S,
+    count: u32,
+}
+
+impl<'a, S: Release + ?Sized> SemaphorePermit<'a, S> {
+    pub(crate) fn new(semaphore: &'a S, count: u32) -> Self {
+        Self { semaphore, count }
+    }
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+    /// Намеренно не возвращает ресурсы семафору
+    pub fn forget(mut self) {
+        self.count = 0;
+    }
+}
+
+impl<S: Release + ?Sized> Drop for SemaphorePermit<'_, S> {
+    fn drop(&mut self) {
+        if self.count != 0 {
+            release(self.semaphore, self.count);
+        }
+    }
+}
+
+fn release<S: Release + ?Sized>(semaphore: &S, n: u32) {
+    let res = semaphore.release(n);
+    debug_assert!(
+        res.is_ok() || std::thread::panicking(),
+        "Ресурсы вернули в уже полный семафор"
+    );
+}
+
+/// Как [`SemaphorePermit`], но владеет семафором через [`Arc`],
+/// поэтому его можно передать в другой поток
+#[must_use]
+pub struct OwnedSemaphorePermit<S: Release + ?Sized> {
+    semaphore: Arc<S>,
+    count: u32,
+}
+
+impl<S: Release + ?Sized> OwnedSemaphorePermit<S> {
+    pub(crate) fn new(semaphore: Arc<S>, count: u32) -> Self {
+        Self { semaphore, count }
+    }
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+    pub fn semaphore(&self) -> &Arc<S> {
+        &self.semaphore
+    }
+    /// Намеренно не возвращает ресурсы семафору
+    pub fn forget(mut self) {
+        self.count = 0;
+    }
+}
+
+impl<S: Release + ?Sized> Drop for OwnedSemaphorePermit<S> {
+    fn drop(&mut self) {
+        if self.count != 0 {
+            release(&*self.semaphore, self.count);
+        }
+    }
+}
+
+#[test]
+fn permit_released_on_panic() {
+    let semaphore = super::Semaphore::new(1);
+    let res = std::panic::catch_unwind(|| {
+        let _permit = semaphore.acquire().unwrap();
+        panic!("паника с занятым ресурсом");
+    });
+    assert!(res.is_err());
+    assert!(semaphore.try_acquire().is_some());
+
+    let semaphore = super::optimised::Semaphore::new(1);
+    let res = std::panic::catch_unwind(|| {
+        let _permit = semaphore.acquire().unwrap();
+        panic!("паника с занятым ресурсом");
+    });
+    assert!(res.is_err());
+    assert!(semaphore.try_acquire().is_some());
+}
+
+#[test]
+fn forget_permit() {
+    let semaphore = super::Semaphore::new(3);
+    semaphore.acquire_many(2).unwrap().forget();
+    assert!(semaphore.try_acquire_many(2).is_none());
+    assert_eq!(semaphore.acquire().unwrap().count(), 1);
+
+    let semaphore = super::optimised::Semaphore::new(3);
+    semaphore.acquire_many(2).unwrap().forget();
+    assert!(semaphore.try_acquire_many(2).is_none());
+    assert_eq!(semaphore.acquire().unwrap().count(), 1);
+}
+
+#[test]
+fn owned_permit_across_threads() {
+    let semaphore = Arc::new(super::optimised::Semaphore::new(2));
+    let permits = (0..2)
+        .map(|_| semaphore.clone().acquire_owned().unwrap())
+        .collect::<Vec<_>>();
+    assert!(semaphore.try_acquire().is_none());
+    let handles = permits
+        .into_iter()
+        .map(|permit| std::thread::spawn(move || drop(permit)))
+        .collect::<Vec<_>>();
+    handles.into_iter().for_each(|h| h.join().unwrap());
+    assert_eq!(semaphore.acquire_many(2).unwrap().count(), 2);
+
+    let semaphore = Arc::new(super::Semaphore::new(1));
+    let permit = semaphore.clone().acquire_owned().unwrap();
+    std::thread::spawn(move || drop(permit)).join().unwrap();
+    assert!(semaphore.try_acquire().is_some());
+}
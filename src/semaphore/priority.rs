@@ -0,0 +1,224 @@
+use super::{
+    counting::CountingSemaphore,
+    handoff::Handoff,
+    permit::{Release, SemaphorePermit},
+    Closed, SemaphoreFull,
+};
+use std::time::{Duration, Instant};
+
+pub use super::handoff::DEFAULT_PRIORITY;
+
+/// Семафор, который отдаёт освободившийся ресурс ожидающему
+/// с наибольшим приоритетом, а среди равных первому пришедшему.
+///
+/// Чтобы низкий приоритет не голодал, за каждые `aging` ожидания
+/// приоритет ожидающего растёт на единицу. Ресурсы передаются напрямую,
+/// как в [`FairSemaphore`](super::FairSemaphore), а свободные считает
+/// обычный [`Semaphore`](super::Semaphore), поэтому API у них общий
+pub struct PrioritySemaphore {
+    queue: Handoff,
+}
+
+const DEFAULT_AGING: Duration = Duration::from_millis(100);
+
+impl PrioritySemaphore {
+    pub const fn new(count_of_resurses: u32) -> Self {
+        Self::with_aging(count_of_resurses, DEFAULT_AGING)
+    }
+    /// Каждые `aging` ожидания повышают приоритет на единицу
+    pub const fn with_aging(count_of_resurses: u32, aging: Duration) -> Self {
+        assert!(!aging.is_zero());
+        Self {
+            queue: Handoff::new(count_of_resurses, Some(aging)),
+        }
+    }
+    pub fn signal(&self) {
+        self.signal_n(1);
+    }
+    /// Возвращает ресурсы. Работает и после закрытия семафора
+    pub fn signal_n(&self, n: u32) {
+        if let Err(e) = self.try_signal_n(n) {
+            panic!("{e}");
+        }
+    }
+    /// Как [`Self::signal`], но вместо паники сообщает о лишнем возврате
+    pub fn try_signal(&self) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(1)
+    }
+    pub fn try_signal_n(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.queue.try_signal_n(n)
+    }
+    /// Ждёт с приоритетом [`DEFAULT_PRIORITY`]
+    pub fn wait(&self) -> Result<(), Closed> {
+        self.wait_with_priority(DEFAULT_PRIORITY)
+    }
+    /// Чем больше `priority`, тем раньше ожидающий получит ресурс
+    pub fn wait_with_priority(&self, priority: u32) -> Result<(), Closed> {
+        self.queue.wait(priority)
+    }
+    /// Забирает ресурс, только если он есть и никто его не ждёт
+    pub fn try_wait(&self) -> bool {
+        self.queue.try_wait()
+    }
+    /// Закрывает семафор: все текущие и будущие ожидания возвращают [`Closed`]
+    pub fn close(&self) {
+        self.queue.close();
+    }
+    pub fn is_closed(&self) -> bool {
+        self.queue.is_closed()
+    }
+    /// Как [`Self::wait_timeout_with_priority`] с приоритетом [`DEFAULT_PRIORITY`]
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        self.wait_timeout_with_priority(DEFAULT_PRIORITY, timeout)
+    }
+    /// Возвращает `false`, если очередь не дошла за `timeout`
+    /// или семафор закрыт
+    pub fn wait_timeout_with_priority(&self, priority: u32, timeout: Duration) -> bool {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.wait_deadline_with_priority(priority, deadline),
+            None => self.wait_with_priority(priority).is_ok(),
+        }
+    }
+    /// Как [`Self::wait_deadline_with_priority`] с приоритетом [`DEFAULT_PRIORITY`]
+    pub fn wait_deadline(&self, deadline: Instant) -> bool {
+        self.wait_deadline_with_priority(DEFAULT_PRIORITY, deadline)
+    }
+    /// Возвращает `false`, если очередь не дошла к `deadline`
+    /// или семафор закрыт. Ушедший по таймауту не держит место в очереди
+    pub fn wait_deadline_with_priority(&self, priority: u32, deadline: Instant) -> bool {
+        self.queue.wait_deadline(priority, deadline)
+    }
+    /// Количество свободных ресурсов
+    pub fn available(&self) -> u32 {
+        self.queue.available()
+    }
+    pub fn acquire(&self) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.acquire_with_priority(DEFAULT_PRIORITY)
+    }
+    pub fn acquire_with_priority(
+        &self,
+        priority: u32,
+    ) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.wait_with_priority(priority)?;
+        Ok(SemaphorePermit::new(self, 1))
+    }
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_, Self>> {
+        self.try_wait().then(|| SemaphorePermit::new(self, 1))
+    }
+}
+
+impl Release for PrioritySemaphore {
+    fn release(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(n)
+    }
+}
+
+impl CountingSemaphore for PrioritySemaphore {
+    fn wait(&self) -> Result<(), Closed> {
+        PrioritySemaphore::wait(self)
+    }
+    fn signal(&self) {
+        PrioritySemaphore::signal(self)
+    }
+    fn try_wait(&self) -> bool {
+        PrioritySemaphore::try_wait(self)
+    }
+    fn available(&self) -> u32 {
+        self.queue.available()
+    }
+}
+
+#[test]
+fn higher_priority_first() {
+    use std::{sync::Mutex, thread};
+
+    // старение не должно вмешиваться в порядок
+    let semaphore = PrioritySemaphore::with_aging(0, Duration::from_secs(3600));
+    let order = Mutex::new(Vec::new());
+    let priorities = [3, 0, 7, 3, 5, 1, 7, 2];
+    thread::scope(|s| {
+        for (i, priority) in priorities.into_iter().enumerate() {
+            let (semaphore, order) = (&semaphore, &order);
+            s.spawn(move || {
+                semaphore.wait_with_priority(priority).unwrap();
+                order.lock().unwrap().push((priority, i));
+            });
+            while semaphore.queue.queued() != i + 1 {
+                thread::yield_now();
+            }
+        }
+        for i in 0..priorities.len() {
+            semaphore.signal();
+            while order.lock().unwrap().len() != i + 1 {
+                thread::yield_now();
+            }
+        }
+    });
+    // по убыванию приоритета, при равенстве в порядке прихода
+    let mut expected = priorities
+        .into_iter()
+        .enumerate()
+        .map(|(i, p)| (p, i))
+        .collect::<Vec<_>>();
+    expected.sort_by_key(|&(p, i)| (std::cmp::Reverse(p), i));
+    assert_eq!(order.into_inner().unwrap(), expected);
+}
+
+#[test]
+fn aging_lets_low_priority_through() {
+    use std::{sync::Mutex, thread};
+
+    const AGING: Duration = Duration::from_millis(10);
+
+    let semaphore = PrioritySemaphore::with_aging(0, AGING);
+    let served = Mutex::new(Vec::new());
+    thread::scope(|s| {
+        let (semaphore, served) = (&semaphore, &served);
+        s.spawn(move || {
+            semaphore.wait_with_priority(0).unwrap();
+            served.lock().unwrap().push(0);
+        });
+        while semaphore.queue.queued() != 1 {
+            thread::yield_now();
+        }
+        // за 5 интервалов низкий приоритет дорастает до 5
+        thread::sleep(AGING * 5);
+        s.spawn(move || {
+            semaphore.wait_with_priority(3).unwrap();
+            served.lock().unwrap().push(3);
+        });
+        while semaphore.queue.queued() != 2 {
+            thread::yield_now();
+        }
+        semaphore.signal();
+        while served.lock().unwrap().len() != 1 {
+            thread::yield_now();
+        }
+        semaphore.signal();
+    });
+    assert_eq!(served.into_inner().unwrap(), [0, 3]);
+}
+
+#[test]
+fn wait_timeout_keeps_priority_order() {
+    use std::thread;
+
+    let semaphore = PrioritySemaphore::with_aging(0, Duration::from_secs(3600));
+    assert!(!semaphore.wait_timeout(Duration::from_millis(10)));
+    assert_eq!(semaphore.queue.queued(), 0);
+    thread::scope(|s| {
+        let low = s.spawn(|| semaphore.wait_timeout_with_priority(1, Duration::from_millis(50)));
+        while semaphore.queue.queued() != 1 {
+            thread::yield_now();
+        }
+        let high = s.spawn(|| semaphore.wait_timeout_with_priority(2, Duration::from_secs(60)));
+        while semaphore.queue.queued() != 2 {
+            thread::yield_now();
+        }
+        // единственный ресурс достаётся старшему, младший уходит по таймауту
+        semaphore.signal();
+        assert!(high.join().unwrap());
+        assert!(!low.join().unwrap());
+    });
+    assert_eq!((semaphore.available(), semaphore.queue.queued()), (0, 0));
+}
@@ -0,0 +1,302 @@
+use super::optimised::Semaphore;
+use std::{
+    sync::atomic::{AtomicU64, Ordering::*},
+    time::{Duration, Instant},
+};
+
+/// Источник времени для [`RateLimiter`], в тестах его подменяют
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    /// Ждёт токен в `tokens` не дольше `timeout`.
+    /// Возвращает `true`, если токен забран
+    fn wait(&self, tokens: &Semaphore, timeout: Duration) -> bool;
+}
+
+/// Настоящее время
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    fn wait(&self, tokens: &Semaphore, timeout: Duration) -> bool {
+        tokens.wait_timeout(timeout)
+    }
+}
+
+/// Ограничитель частоты по схеме token bucket.
+///
+/// Токены лежат в счётчике [`optimised::Semaphore`](Semaphore) с максимумом `burst`.
+/// Фонового потока нет: накопившиеся с прошлого раза токены досыпаются
+/// тем, кто пришёл за ресурсом, а остальные ждут их на futex семафора.
+///
+/// Старший бит счётчика семафора занят признаком закрытия,
+/// поэтому `burst` не больше [`RateLimiter::MAX_BURST`]
+pub struct RateLimiter<C: Clock = SystemClock> {
+    tokens: Semaphore,
+    burst: u32,
+    // наносекунды одного токена
+    interval: u64,
+    clock: C,
+    start: Instant,
+    // момент (от `start`), до которого токены уже начислены
+    refilled: AtomicU64,
+}
+
+impl RateLimiter {
+    /// Наибольший запас токенов
+    pub const MAX_BURST: u32 = (1 << 31) - 1;
+
+    /// Один токен каждые `interval`, но не больше `burst` про запас.
+    /// Изначально запас полный
+    pub fn new(interval: Duration, burst: u32) -> Self {
+        Self::with_clock(interval, burst, SystemClock)
+    }
+}
+
+impl<C: Clock> RateLimiter<C> {
+    pub fn with_clock(interval: Duration, burst: u32, clock: C) -> Self {
+        let interval = u64::try_from(interval.as_nanos()).unwrap();
+        assert!(interval != 0 && burst != 0);
+        assert!(
+            burst <= <RateLimiter>::MAX_BURST,
+            "burst {burst} больше RateLimiter::MAX_BURST"
+        );
+        Self {
+            tokens: Semaphore::with_max(burst, burst),
+            burst,
+            interval,
+            start: clock.now(),
+            clock,
+            refilled: AtomicU64::new(0),
+        }
+    }
+    /// Забирает токен, засыпая до появления следующего.
+    /// Токен, который досыпал другой поток, будит раньше срока
+    pub fn acquire(&self) {
+        while !self.try_acquire() {
+            if self.clock.wait(&self.tokens, self.until_next()) {
+                return;
+            }
+        }
+    }
+    /// Забирает токен, только если он есть прямо сейчас
+    pub fn try_acquire(&self) -> bool {
+        self.refill();
+        self.tokens.try_wait()
+    }
+    /// Возвращает `false`, если за `timeout` токен получить не удалось
+    pub fn acquire_timeout(&self, timeout: Duration) -> bool {
+        let Some(deadline) = self.clock.now().checked_add(timeout) else {
+            // такой срок не наступит никогда
+            self.acquire();
+            return true;
+        };
+        loop {
+            if self.try_acquire() {
+                return true;
+            }
+            let next = self.until_next();
+            // токены появляются только со временем, поэтому ждать дальше бессмысленно
+            if self.clock.now() + next > deadline {
+                return false;
+            }
+            if self.clock.wait(&self.tokens, next) {
+                return true;
+            }
+        }
+    }
+    /// Сколько токенов можно забрать прямо сейчас
+    pub fn available(&self) -> u32 {
+        self.refill();
+        self.tokens.available()
+    }
+    fn elapsed(&self) -> u64 {
+        let elapsed = self.clock.now().saturating_duration_since(self.start);
+        u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+    }
+    fn until_next(&self) -> Duration {
+        let since = self.elapsed().saturating_sub(self.refilled.load(Acquire));
+        Duration::from_nanos(self.interval.saturating_sub(since).max(1))
+    }
+    /// Начисляет токены за время с прошлого начисления.
+    /// Что не влезло в `burst`, сгорает
+    fn refill(&self) {
+        let now = self.elapsed();
+        let mut refilled = self.refilled.load(Acquire);
+        let tokens = loop {
+            let tokens = now.saturating_sub(refilled) / self.interval;
+            if tokens == 0 {
+                return;
+            }
+            match self.refilled.compare_exchange_weak(
+                refilled,
+                refilled + tokens * self.interval,
+                AcqRel,
+                Acquire,
+            ) {
+                Ok(_) => break tokens,
+                Err(e) => refilled = e,
+            }
+        };
+        let mut tokens = tokens.min(self.burst as u64) as u32;
+        // другие потоки могут забирать и досыпать токены параллельно
+        while tokens != 0 {
+            let space = self.burst - self.tokens.available();
+            tokens = tokens.min(space);
+            if tokens == 0 || self.tokens.try_signal_n(tokens).is_ok() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone)]
+struct MockClock(std::sync::Arc<std::sync::Mutex<Instant>>);
+
+#[cfg(test)]
+impl MockClock {
+    fn new() -> Self {
+        Self(std::sync::Arc::new(std::sync::Mutex::new(Instant::now())))
+    }
+    fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+    /// Время идёт мгновенно
+    fn wait(&self, tokens: &Semaphore, timeout: Duration) -> bool {
+        self.advance(timeout);
+        tokens.try_wait()
+    }
+}
+
+#[test]
+fn burst_then_rate() {
+    let clock = MockClock::new();
+    let limiter = RateLimiter::with_clock(Duration::from_millis(100), 3, clock.clone());
+    for _ in 0..3 {
+        assert!(limiter.try_acquire());
+    }
+    assert!(!limiter.try_acquire());
+
+    clock.advance(Duration::from_millis(99));
+    assert!(!limiter.try_acquire());
+    clock.advance(Duration::from_millis(1));
+    assert!(limiter.try_acquire());
+    assert!(!limiter.try_acquire());
+
+    // долгий простой не копит больше `burst`
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(limiter.available(), 3);
+}
+
+#[test]
+fn partial_interval_is_not_lost() {
+    let clock = MockClock::new();
+    let limiter = RateLimiter::with_clock(Duration::from_millis(100), 1, clock.clone());
+    assert!(limiter.try_acquire());
+    for _ in 0..10 {
+        clock.advance(Duration::from_millis(150));
+        assert!(limiter.try_acquire());
+        clock.advance(Duration::from_millis(50));
+        assert!(limiter.try_acquire());
+    }
+}
+
+#[test]
+fn acquire_sleeps_until_next_token() {
+    let clock = MockClock::new();
+    let limiter = RateLimiter::with_clock(Duration::from_millis(100), 2, clock.clone());
+    let start = clock.now();
+    for _ in 0..12 {
+        limiter.acquire();
+    }
+    // два из запаса и по одному на каждые 100мс
+    assert_eq!(clock.now() - start, Duration::from_millis(1_000));
+}
+
+#[test]
+fn acquire_timeout() {
+    let clock = MockClock::new();
+    let limiter = RateLimiter::with_clock(Duration::from_millis(100), 1, clock.clone());
+    assert!(limiter.acquire_timeout(Duration::ZERO));
+    let start = clock.now();
+    assert!(!limiter.acquire_timeout(Duration::from_millis(50)));
+    // ожидание безнадёжно, поэтому время не тратится
+    assert_eq!(clock.now(), start);
+    assert!(limiter.acquire_timeout(Duration::from_millis(100)));
+    assert_eq!(clock.now() - start, Duration::from_millis(100));
+    // бесконечный таймаут это обычное ожидание
+    assert!(limiter.acquire_timeout(Duration::MAX));
+    assert_eq!(clock.now() - start, Duration::from_millis(200));
+}
+
+#[test]
+#[should_panic = "MAX_BURST"]
+fn burst_limit() {
+    RateLimiter::new(Duration::from_millis(1), <RateLimiter>::MAX_BURST + 1);
+}
+
+#[test]
+fn concurrent_tokens_match_elapsed_time() {
+    use std::thread;
+
+    let clock = MockClock::new();
+    let limiter = RateLimiter::with_clock(Duration::from_millis(10), 5, clock.clone());
+    let taken = std::sync::atomic::AtomicU32::new(0);
+    thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| {
+                for _ in 0..1_000 {
+                    if limiter.try_acquire() {
+                        taken.fetch_add(1, Relaxed);
+                    }
+                }
+            });
+        }
+        for _ in 0..100 {
+            clock.advance(Duration::from_millis(10));
+            thread::yield_now();
+        }
+    });
+    // не больше запаса и токена на каждый прошедший интервал
+    let taken = taken.into_inner() + limiter.available();
+    assert!(taken <= 5 + 100, "{taken}");
+}
+
+#[test]
+fn real_clock() {
+    let limiter = RateLimiter::new(Duration::from_millis(5), 1);
+    let start = Instant::now();
+    for _ in 0..5 {
+        limiter.acquire();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+fn real_clock_concurrent() {
+    use std::thread;
+
+    let limiter = RateLimiter::new(Duration::from_millis(2), 1);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..5 {
+                    limiter.acquire();
+                }
+            });
+        }
+    });
+    // один токен из запаса, остальные по одному за интервал
+    assert!(start.elapsed() >= Duration::from_millis(2 * 19));
+}
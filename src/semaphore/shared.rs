@@ -0,0 +1,270 @@
+use super::{
+    counting::CountingSemaphore,
+    futex,
+    permit::{Release, SemaphorePermit},
+    Closed, SemaphoreFull,
+};
+use std::{
+    fs::File,
+    io,
+    os::fd::AsRawFd,
+    path::Path,
+    ptr,
+    sync::atomic::{AtomicU32, Ordering::*},
+    time::{Duration, Instant},
+};
+
+/// Семафор, счётчик которого лежит в отображённом в память файле,
+/// поэтому один лимит могут делить несколько процессов на одной машине.
+///
+/// Устроен как [`optimised::Semaphore`](super::optimised::Semaphore), но futex
+/// не приватный: atomic-wait ставит `FUTEX_PRIVATE_FLAG`, и пробуждение
+/// из другого процесса до спящего не дошло бы
+pub struct SharedSemaphore {
+    // старший бит это признак закрытия, как в `optimised::Semaphore`
+    counter: *const AtomicU32,
+}
+
+unsafe impl Send for SharedSemaphore {}
+unsafe impl Sync for SharedSemaphore {}
+
+const CLOSED: u32 = 1 << 31;
+const SIZE: usize = size_of::<AtomicU32>();
+
+impl SharedSemaphore {
+    /// Создаёт файл (или перезаписывает существующий) с `count_of_resurses` ресурсами
+    pub fn create(path: impl AsRef<Path>, count_of_resurses: u32) -> io::Result<Self> {
+        assert!(count_of_resurses < CLOSED);
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(SIZE as u64)?;
+        let semaphore = Self::map(&file)?;
+        semaphore.counter().store(count_of_resurses, Release);
+        Ok(semaphore)
+    }
+    /// Подключается к семафору, который создал другой процесс
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::options().read(true).write(true).open(path)?;
+        if file.metadata()?.len() < SIZE as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "файл слишком мал для семафора",
+            ));
+        }
+        Self::map(&file)
+    }
+    fn map(file: &File) -> io::Result<Self> {
+        // отображение остаётся действительным и после закрытия файла
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            counter: ptr as *const AtomicU32,
+        })
+    }
+    fn counter(&self) -> &AtomicU32 {
+        unsafe { &*self.counter }
+    }
+    pub fn signal(&self) {
+        self.signal_n(1);
+    }
+    /// Возвращает сразу `n` ресурсов. Работает и после закрытия семафора
+    pub fn signal_n(&self, n: u32) {
+        if let Err(e) = self.try_signal_n(n) {
+            panic!("{e}");
+        }
+    }
+    /// Как [`Self::signal_n`], но вместо паники сообщает о переполнении счётчика
+    pub fn try_signal_n(&self, n: u32) -> Result<(), SemaphoreFull> {
+        let mut c = self.counter().load(Relaxed);
+        loop {
+            let new = (c & !CLOSED)
+                .checked_add(n)
+                .filter(|&new| new < CLOSED)
+                .ok_or(SemaphoreFull)?;
+            match self
+                .counter()
+                .compare_exchange(c, new | (c & CLOSED), Release, Relaxed)
+            {
+                Ok(_) => break,
+                Err(e) => c = e,
+            }
+        }
+        futex::wake(self.counter(), n.min(i32::MAX as u32) as i32);
+        Ok(())
+    }
+    pub fn wait(&self) -> Result<(), Closed> {
+        while !self.take()? {
+            futex::wait(self.counter(), 0, None);
+        }
+        Ok(())
+    }
+    /// Забирает ресурс, только если он есть прямо сейчас
+    pub fn try_wait(&self) -> bool {
+        self.take() == Ok(true)
+    }
+    /// Возвращает `false`, если ресурс не освободился за `timeout`
+    /// или семафор закрыт
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.wait_deadline(deadline),
+            None => self.wait().is_ok(),
+        }
+    }
+    /// Возвращает `false`, если ресурс не освободился к `deadline`
+    /// или семафор закрыт
+    pub fn wait_deadline(&self, deadline: Instant) -> bool {
+        loop {
+            match self.take() {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(Closed) => return false,
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            futex::wait(self.counter(), 0, Some(deadline - now));
+        }
+    }
+    fn take(&self) -> Result<bool, Closed> {
+        let mut c = self.counter().load(Relaxed);
+        loop {
+            if c & CLOSED != 0 {
+                return Err(Closed);
+            }
+            if c == 0 {
+                return Ok(false);
+            }
+            match self
+                .counter()
+                .compare_exchange_weak(c, c - 1, Acquire, Relaxed)
+            {
+                Ok(_) => return Ok(true),
+                Err(e) => c = e,
+            }
+        }
+    }
+    /// Закрывает семафор во всех процессах: текущие и будущие ожидания
+    /// возвращают [`Closed`]
+    pub fn close(&self) {
+        self.counter().fetch_or(CLOSED, Release);
+        futex::wake(self.counter(), i32::MAX);
+    }
+    pub fn is_closed(&self) -> bool {
+        self.counter().load(Acquire) & CLOSED != 0
+    }
+    /// Количество свободных ресурсов
+    pub fn available(&self) -> u32 {
+        self.counter().load(Relaxed) & !CLOSED
+    }
+    /// Забирает ресурс, который вернётся при удалении [`SemaphorePermit`]
+    pub fn acquire(&self) -> Result<SemaphorePermit<'_, Self>, Closed> {
+        self.wait()?;
+        Ok(SemaphorePermit::new(self, 1))
+    }
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_, Self>> {
+        self.try_wait().then(|| SemaphorePermit::new(self, 1))
+    }
+}
+
+impl Drop for SharedSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.counter as *mut libc::c_void, SIZE);
+        }
+    }
+}
+
+impl Release for SharedSemaphore {
+    fn release(&self, n: u32) -> Result<(), SemaphoreFull> {
+        self.try_signal_n(n)
+    }
+}
+
+impl CountingSemaphore for SharedSemaphore {
+    fn wait(&self) -> Result<(), Closed> {
+        SharedSemaphore::wait(self)
+    }
+    fn signal(&self) {
+        SharedSemaphore::signal(self)
+    }
+    fn try_wait(&self) -> bool {
+        SharedSemaphore::try_wait(self)
+    }
+    fn available(&self) -> u32 {
+        SharedSemaphore::available(self)
+    }
+}
+
+#[cfg(test)]
+const CHILD_ENV: &str = "LF_STRUCTS_SHARED_SEMAPHORE";
+
+/// Тело дочернего процесса для [`across_processes`].
+/// При обычном запуске тестов ничего не делает
+#[test]
+fn child_process() {
+    let Ok(path) = std::env::var(CHILD_ENV) else {
+        return;
+    };
+    let to_child = SharedSemaphore::open(&path).unwrap();
+    let to_parent = SharedSemaphore::open(format!("{path}.back")).unwrap();
+    for _ in 0..100 {
+        to_child.wait().unwrap();
+    }
+    to_parent.signal();
+}
+
+#[test]
+fn across_processes() {
+    use std::process::{Command, Stdio};
+
+    const CHILDREN: u32 = 3;
+
+    let path = std::env::temp_dir().join(format!("lf-structs-semaphore-{}", std::process::id()));
+    let back = format!("{}.back", path.display());
+    let to_children = SharedSemaphore::create(&path, 0).unwrap();
+    let to_parent = SharedSemaphore::create(&back, 0).unwrap();
+    let exe = std::env::current_exe().unwrap();
+    let mut children = (0..CHILDREN)
+        .map(|_| {
+            Command::new(&exe)
+                .args(["--exact", "semaphore::shared::child_process"])
+                .env(CHILD_ENV, &path)
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    // дети спят на futex, пока родитель не отдаст ресурсы
+    to_children.signal_n(CHILDREN * 100);
+    // а родитель спит, пока каждый ребёнок не заберёт свои
+    for _ in 0..CHILDREN {
+        assert!(
+            to_parent.wait_timeout(Duration::from_secs(30)),
+            "пробуждение не дошло до другого процесса"
+        );
+    }
+    for child in &mut children {
+        assert!(child.wait().unwrap().success());
+    }
+    assert_eq!(to_children.available(), 0);
+    assert_eq!(to_parent.available(), 0);
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(back).unwrap();
+}
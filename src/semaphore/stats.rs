@@ -0,0 +1,88 @@
+//! Статистика ожиданий семафоров, включается фичей `stats`
+use std::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering::*},
+    time::Duration,
+};
+
+/// Количество корзин гистограммы: корзина `i` считает ожидания
+/// короче `2^i` мкс, последняя считает все остальные
+pub const BUCKETS: usize = 24;
+
+pub(crate) struct WaitStats {
+    histogram: [AtomicU64; BUCKETS],
+    peak_waiters: AtomicU32,
+}
+
+/// Снимок статистики семафора
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub histogram: [u64; BUCKETS],
+    /// Наибольшее число одновременно ждавших потоков
+    pub peak_waiters: u32,
+}
+
+impl Stats {
+    /// Верхняя граница корзины `i` гистограммы
+    pub fn bucket_bound(i: usize) -> Duration {
+        if i + 1 == BUCKETS {
+            Duration::MAX
+        } else {
+            Duration::from_micros(1 << i)
+        }
+    }
+    /// Сколько всего было успешных ожиданий
+    pub fn waits(&self) -> u64 {
+        self.histogram.iter().sum()
+    }
+}
+
+impl WaitStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            histogram: [const { AtomicU64::new(0) }; BUCKETS],
+            peak_waiters: AtomicU32::new(0),
+        }
+    }
+    pub(crate) fn record_wait(&self, waited: Duration) {
+        let micros = waited.as_micros();
+        let bucket = (u128::BITS - micros.leading_zeros()) as usize;
+        self.histogram[bucket.min(BUCKETS - 1)].fetch_add(1, Relaxed);
+    }
+    pub(crate) fn record_waiters(&self, waiters: u32) {
+        self.peak_waiters.fetch_max(waiters, Relaxed);
+    }
+    pub(crate) fn snapshot(&self) -> Stats {
+        Stats {
+            histogram: self.histogram.each_ref().map(|b| b.load(Relaxed)),
+            peak_waiters: self.peak_waiters.load(Relaxed),
+        }
+    }
+    pub(crate) fn reset(&self) {
+        self.histogram.iter().for_each(|b| b.store(0, Relaxed));
+        self.peak_waiters.store(0, Relaxed);
+    }
+}
+
+#[test]
+fn histogram_buckets() {
+    let stats = WaitStats::new();
+    stats.record_wait(Duration::ZERO);
+    stats.record_wait(Duration::from_nanos(999));
+    stats.record_wait(Duration::from_micros(1));
+    stats.record_wait(Duration::from_micros(3));
+    stats.record_wait(Duration::from_secs(3600));
+    stats.record_waiters(3);
+    stats.record_waiters(1);
+
+    let snapshot = stats.snapshot();
+    assert_eq!(&snapshot.histogram[..3], [2, 1, 1]);
+    assert_eq!(snapshot.histogram[BUCKETS - 1], 1);
+    assert_eq!(snapshot.waits(), 5);
+    assert_eq!(snapshot.peak_waiters, 3);
+    // каждое ожидание лежит в корзине, граница которой его превышает
+    assert!(Duration::from_micros(3) < Stats::bucket_bound(2));
+    assert!(Duration::from_micros(1) >= Stats::bucket_bound(0));
+
+    stats.reset();
+    assert_eq!(stats.snapshot().waits(), 0);
+}